@@ -0,0 +1,7 @@
+// Migrations, templates and static files are embedded with `include_dir!`,
+// which does not tell cargo to rebuild when they change.
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=src/front/templates");
+    println!("cargo:rerun-if-changed=src/front/public");
+}
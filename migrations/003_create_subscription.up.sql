@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS subscription (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    source_account      TEXT NOT NULL,
+    counterparty        TEXT NOT NULL,
+    currency            TEXT NOT NULL,
+    period              TEXT NOT NULL,
+    occurrences         INTEGER NOT NULL,
+    first_date          DATE NOT NULL,
+    last_date           DATE NOT NULL,
+    next_expected_date  DATE NOT NULL,
+    average_amount      NUMERIC NOT NULL,
+    last_amount         NUMERIC NOT NULL,
+    annual_cost         NUMERIC NOT NULL,
+    price_increased     BOOLEAN NOT NULL,
+    stopped             BOOLEAN NOT NULL
+)
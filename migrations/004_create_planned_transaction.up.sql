@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS planned_transaction (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    account_id          UUID NOT NULL REFERENCES account(id),
+    name                TEXT NOT NULL,
+    amount              NUMERIC NOT NULL,
+    period              TEXT NOT NULL,
+    next_date           DATE NOT NULL
+)
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS payee (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    name                TEXT NOT NULL UNIQUE
+)
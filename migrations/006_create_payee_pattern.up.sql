@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS payee_pattern (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    payee_id            UUID NOT NULL REFERENCES payee(id) ON DELETE CASCADE,
+    kind                TEXT NOT NULL,
+    pattern             TEXT NOT NULL
+)
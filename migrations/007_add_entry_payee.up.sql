@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS payee_id UUID REFERENCES payee(id) ON DELETE SET NULL
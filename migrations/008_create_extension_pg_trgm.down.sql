@@ -0,0 +1,3 @@
+-- pg_trgm is kept, it may have been installed before this migration and
+-- other objects of the database may use it
+SELECT 1
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS entry_search_idx
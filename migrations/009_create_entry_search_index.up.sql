@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS entry_search_idx ON entry USING GIN ((title || ' ' || sender_or_receiver || ' ' || address) gin_trgm_ops)
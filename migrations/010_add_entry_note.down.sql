@@ -0,0 +1 @@
+ALTER TABLE entry DROP COLUMN IF EXISTS note
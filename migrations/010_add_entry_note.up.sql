@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS note TEXT NOT NULL DEFAULT ''
@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS tags TEXT[] NOT NULL DEFAULT '{}'
@@ -0,0 +1 @@
+ALTER TABLE entry DROP COLUMN IF EXISTS excluded_from_reports
@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS excluded_from_reports BOOLEAN NOT NULL DEFAULT FALSE
@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS original_title TEXT
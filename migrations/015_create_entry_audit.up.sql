@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS entry_audit (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    entry_id            UUID NOT NULL REFERENCES entry(id) ON DELETE CASCADE,
+    field               TEXT NOT NULL,
+    old_value           TEXT NOT NULL,
+    new_value           TEXT NOT NULL,
+    changed_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)
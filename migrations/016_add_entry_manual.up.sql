@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS manual BOOLEAN NOT NULL DEFAULT FALSE
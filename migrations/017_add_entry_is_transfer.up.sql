@@ -0,0 +1 @@
+ALTER TABLE entry ADD COLUMN IF NOT EXISTS is_transfer BOOLEAN NOT NULL DEFAULT FALSE
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS bulk_action (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    action              TEXT NOT NULL,
+    snapshot            JSONB NOT NULL,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)
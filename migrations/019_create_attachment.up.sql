@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS attachment (
+    id                  UUID NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    entry_id            UUID NOT NULL REFERENCES entry(id) ON DELETE CASCADE,
+    file_name           TEXT NOT NULL,
+    content_type        TEXT NOT NULL,
+    size                BIGINT NOT NULL,
+    created_at          TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS duplicate_dismissal (
+    entry_a             UUID NOT NULL REFERENCES entry(id) ON DELETE CASCADE,
+    entry_b             UUID NOT NULL REFERENCES entry(id) ON DELETE CASCADE,
+    dismissed_at        TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (entry_a, entry_b)
+)
@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS merged_reference (
+    reference_number    TEXT NOT NULL PRIMARY KEY,
+    entry_id            UUID NOT NULL REFERENCES entry(id) ON DELETE CASCADE,
+    merged_at           TIMESTAMPTZ NOT NULL DEFAULT NOW()
+)
@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS exchange_rate (
+    currency            TEXT NOT NULL,
+    quote               TEXT NOT NULL,
+    rate_date           DATE NOT NULL,
+    rate                NUMERIC NOT NULL CHECK (rate > 0),
+    source              TEXT NOT NULL,
+    PRIMARY KEY (currency, quote, rate_date)
+)
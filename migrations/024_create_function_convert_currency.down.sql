@@ -0,0 +1 @@
+DROP FUNCTION IF EXISTS convert_currency(NUMERIC, TEXT, TEXT, DATE)
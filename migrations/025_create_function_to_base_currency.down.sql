@@ -0,0 +1 @@
+DROP FUNCTION IF EXISTS to_base_currency(NUMERIC, TEXT, DATE)
@@ -0,0 +1,12 @@
+CREATE OR REPLACE FUNCTION to_base_currency(
+    amount NUMERIC,
+    currency TEXT,
+    on_date DATE
+) RETURNS NUMERIC AS $$
+    SELECT convert_currency(
+        amount,
+        currency,
+        COALESCE((SELECT value FROM setting WHERE key = 'base_currency'), 'PLN'),
+        on_date
+    )
+$$ LANGUAGE sql STABLE STRICT
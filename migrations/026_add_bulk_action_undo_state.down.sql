@@ -0,0 +1,3 @@
+ALTER TABLE bulk_action
+    DROP COLUMN IF EXISTS changed,
+    DROP COLUMN IF EXISTS dependents
@@ -0,0 +1,3 @@
+ALTER TABLE bulk_action
+    ADD COLUMN IF NOT EXISTS changed JSONB NOT NULL DEFAULT '[]',
+    ADD COLUMN IF NOT EXISTS dependents JSONB NOT NULL DEFAULT '{}'
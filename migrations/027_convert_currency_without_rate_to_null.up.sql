@@ -0,0 +1,48 @@
+CREATE OR REPLACE FUNCTION convert_currency(
+    amount NUMERIC,
+    from_currency TEXT,
+    to_currency TEXT,
+    on_date DATE
+) RETURNS NUMERIC AS $$
+DECLARE
+    r NUMERIC;
+BEGIN
+    IF from_currency = to_currency THEN
+        RETURN amount;
+    END IF;
+
+    -- one unit of `currency` is worth `rate` of `quote`, the last rate published
+    -- on or before the date is used
+    SELECT x.rate INTO r
+    FROM exchange_rate x
+    WHERE x.currency = from_currency AND x.quote = to_currency AND x.rate_date <= on_date
+    ORDER BY x.rate_date DESC
+    LIMIT 1;
+    IF r IS NOT NULL THEN
+        RETURN amount * r;
+    END IF;
+
+    SELECT 1 / x.rate INTO r
+    FROM exchange_rate x
+    WHERE x.currency = to_currency AND x.quote = from_currency AND x.rate_date <= on_date
+    ORDER BY x.rate_date DESC
+    LIMIT 1;
+    IF r IS NOT NULL THEN
+        RETURN amount * r;
+    END IF;
+
+    -- both quoted in a third currency, e.g. NBP rates in PLN when the base currency is EUR
+    SELECT f.rate / t.rate INTO r
+    FROM exchange_rate f
+    JOIN exchange_rate t ON t.quote = f.quote AND t.rate_date = f.rate_date
+    WHERE f.currency = from_currency AND t.currency = to_currency AND f.rate_date <= on_date
+    ORDER BY f.rate_date DESC
+    LIMIT 1;
+    IF r IS NOT NULL THEN
+        RETURN amount * r;
+    END IF;
+
+    -- left out of sums, `currency::unconverted` lists such entries
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql STABLE STRICT
@@ -0,0 +1,13 @@
+CREATE OR REPLACE FUNCTION to_base_currency(
+    amount NUMERIC,
+    currency TEXT,
+    on_date DATE
+) RETURNS NUMERIC AS $$
+    -- saved on startup by `currency::init_base_currency`, NULL like a missing rate otherwise
+    SELECT convert_currency(
+        amount,
+        currency,
+        (SELECT value FROM setting WHERE key = 'base_currency'),
+        on_date
+    )
+$$ LANGUAGE sql STABLE STRICT
@@ -0,0 +1,156 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use anyhow::Context;
+use include_dir::{include_dir, Dir};
+
+static MIGRATIONS: Dir = include_dir!("$CARGO_MANIFEST_DIR/migrations");
+static TEMPLATES: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/front/templates");
+static PUBLIC: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/front/public");
+
+/// When set, files are read from this directory instead of the ones built
+/// into the binary, so templates and migrations can be edited without a rebuild.
+static OVERRIDE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Migrations,
+    Templates,
+    Public,
+}
+
+impl Kind {
+    /// Location relative to the repository root, also inside the override directory.
+    fn path(&self) -> &'static str {
+        match self {
+            Kind::Migrations => "migrations",
+            Kind::Templates => "src/front/templates",
+            Kind::Public => "src/front/public",
+        }
+    }
+
+    fn embedded(&self) -> &'static Dir<'static> {
+        match self {
+            Kind::Migrations => &MIGRATIONS,
+            Kind::Templates => &TEMPLATES,
+            Kind::Public => &PUBLIC,
+        }
+    }
+}
+
+pub struct File {
+    /// Path relative to the directory of its kind, e.g. `fonts/upload.svg`.
+    pub name: String,
+    /// Where the file comes from, for messages.
+    pub location: String,
+    pub contents: Cow<'static, [u8]>,
+}
+
+impl File {
+    pub fn text(&self) -> anyhow::Result<&str> {
+        std::str::from_utf8(&self.contents)
+            .with_context(|| format!("{} is not valid UTF-8", self.location))
+    }
+}
+
+/// Can be called once, before any file is read.
+pub fn set_override_dir(dir: PathBuf) -> anyhow::Result<()> {
+    let dir = dir
+        .canonicalize()
+        .with_context(|| format!("override directory {} does not exist", dir.display()))?;
+    OVERRIDE_DIR
+        .set(dir)
+        .map_err(|_| anyhow::anyhow!("override directory is already set"))
+}
+
+/// Directory on disk of `kind`: the override directory or the source tree the
+/// binary was built from. New files, e.g. migrations, are created there.
+pub fn dir(kind: Kind) -> PathBuf {
+    match OVERRIDE_DIR.get() {
+        Some(dir) => dir.join(kind.path()),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join(kind.path()),
+    }
+}
+
+/// Files directly in the directory of `kind`, without subdirectories.
+pub fn list(kind: Kind) -> anyhow::Result<Vec<File>> {
+    if OVERRIDE_DIR.get().is_none() {
+        return Ok(kind
+            .embedded()
+            .files()
+            .map(|f| File {
+                name: f.path().to_string_lossy().to_string(),
+                location: format!("embedded {}/{}", kind.path(), f.path().display()),
+                contents: Cow::Borrowed(f.contents()),
+            })
+            .collect());
+    }
+
+    let dir = dir(kind);
+    let mut files = Vec::new();
+    for entry in fs::read_dir(&dir).with_context(|| format!("cannot read {}", dir.display()))? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        files.push(File {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            location: path.display().to_string(),
+            contents: Cow::Owned(
+                fs::read(&path).with_context(|| format!("cannot read {}", path.display()))?,
+            ),
+        });
+    }
+    Ok(files)
+}
+
+/// File `name` (may contain subdirectories) of `kind`, none when it does not exist.
+pub fn get(kind: Kind, name: &str) -> anyhow::Result<Option<File>> {
+    let name = Path::new(name);
+    if !name.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Ok(None);
+    }
+
+    if OVERRIDE_DIR.get().is_none() {
+        return Ok(kind.embedded().get_file(name).map(|f| File {
+            name: f.path().to_string_lossy().to_string(),
+            location: format!("embedded {}/{}", kind.path(), f.path().display()),
+            contents: Cow::Borrowed(f.contents()),
+        }));
+    }
+
+    let path = dir(kind).join(name);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    Ok(Some(File {
+        name: name.to_string_lossy().to_string(),
+        location: path.display().to_string(),
+        contents: Cow::Owned(
+            fs::read(&path).with_context(|| format!("cannot read {}", path.display()))?,
+        ),
+    }))
+}
+
+/// Content type of static files by their extension.
+pub fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|e| e.to_str()) {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("ico") => "image/x-icon",
+        Some("woff2") => "font/woff2",
+        Some("json") => "application/json",
+        Some("html" | "hbs") => "text/html; charset=utf-8",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,41 @@
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+/// Content type recognized from the first bytes of the file, the name and the
+/// type sent by the browser are not trusted.
+pub fn content_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]) {
+        Some("image/png")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+/// Files are stored in `dir` (`Config::attachments_dir`), named by the id of
+/// their `attachment` row.
+fn path(dir: &Path, id: Uuid) -> PathBuf {
+    dir.join(id.to_string())
+}
+
+pub async fn save(dir: &Path, id: Uuid, bytes: &[u8]) -> anyhow::Result<()> {
+    tokio::fs::create_dir_all(dir).await?;
+    tokio::fs::write(path(dir, id), bytes).await?;
+    Ok(())
+}
+
+pub async fn read(dir: &Path, id: Uuid) -> anyhow::Result<Vec<u8>> {
+    Ok(tokio::fs::read(path(dir, id)).await?)
+}
+
+/// A file which is already gone is not an error.
+pub async fn remove(dir: &Path, id: Uuid) -> anyhow::Result<()> {
+    match tokio::fs::remove_file(path(dir, id)).await {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,262 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use sqlx::{Pool, Postgres};
+
+use crate::{config, currency, export, front::reports, import, migration};
+
+/// Personal budget built from bank statement exports. Starts the web server
+/// when no command is given. Every command but `migrate` applies pending
+/// migrations first.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Read migrations, templates and static files from this directory (the
+    /// repository root) instead of the ones built into the binary
+    #[arg(long, global = true, env = "BUDGETV2_OVERRIDE_DIR")]
+    pub override_dir: Option<PathBuf>,
+
+    /// Reload templates when they are edited. Assets are read from the source
+    /// tree the binary was built from, unless --override-dir is given
+    #[arg(long, global = true, env = "BUDGETV2_DEV")]
+    pub dev: bool,
+
+    #[command(flatten)]
+    pub config: config::Args,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Apply pending migrations and start the web server
+    Serve,
+    /// Import a bank statement, like the upload on the entries page
+    Import {
+        #[arg(long, value_enum, default_value_t)]
+        format: import::Format,
+        /// Name or number of the account the statement should belong to. It is
+        /// only checked, the import fails when the file has entries of other
+        /// accounts; entries keep the account from the file
+        #[arg(long)]
+        account: Option<String>,
+        file: PathBuf,
+    },
+    /// Export entries
+    Export {
+        /// First accounting date, YYYY-MM-DD
+        #[arg(long)]
+        from: NaiveDate,
+        /// Last accounting date, YYYY-MM-DD
+        #[arg(long)]
+        to: NaiveDate,
+        #[arg(long, value_enum, default_value_t)]
+        format: export::Format,
+        /// Written to standard output when not given
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Print a report, amounts are in the base currency
+    Report {
+        #[command(subcommand)]
+        command: ReportCommand,
+    },
+    /// Manage the database schema
+    Migrate {
+        #[command(subcommand)]
+        command: MigrateCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MigrateCommand {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the last applied migrations, meant for development: reverting
+    /// a migration which added a table or a column drops its data
+    Down {
+        /// Number of migrations to revert
+        #[arg(default_value_t = 1)]
+        n: usize,
+        /// Revert even when tables or columns with data would be dropped
+        #[arg(long)]
+        yes: bool,
+    },
+    /// List migrations and whether they are applied
+    Status,
+    /// Create empty up and down files for a new migration
+    New {
+        /// Short description, e.g. add_entry_location
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ReportCommand {
+    /// Income, expenses and savings per month
+    Monthly {
+        /// First month, YYYY-MM [default: eleven months before --to]
+        #[arg(long)]
+        from: Option<String>,
+        /// Last month, YYYY-MM [default: the current month]
+        #[arg(long)]
+        to: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ReportFormat {
+    /// Aligned columns for reading
+    #[default]
+    Table,
+    Csv,
+}
+
+pub async fn import(
+    p: &Pool<Postgres>,
+    format: import::Format,
+    account: Option<&str>,
+    file: &Path,
+) -> anyhow::Result<()> {
+    let f = fs::File::open(file).with_context(|| format!("cannot open {}", file.display()))?;
+    let count = import::load(p, format, f, account).await?;
+    import::after_load(p).await;
+    println!("{} entries imported", count);
+    Ok(())
+}
+
+pub async fn export(
+    p: &Pool<Postgres>,
+    from: NaiveDate,
+    to: NaiveDate,
+    format: export::Format,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    if from > to {
+        anyhow::bail!("--from is after --to");
+    }
+    match output {
+        Some(path) => {
+            let f = fs::File::create(path)
+                .with_context(|| format!("cannot create {}", path.display()))?;
+            let count = export::entries(p, format, from, to, f).await?;
+            println!("{} entries exported to {}", count, path.display());
+        }
+        None => {
+            export::entries(p, format, from, to, io::stdout().lock()).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn report(p: &Pool<Postgres>, command: ReportCommand) -> anyhow::Result<()> {
+    match command {
+        ReportCommand::Monthly { from, to, format } => {
+            let (from, to) = reports::month_range(from.as_deref(), to.as_deref())?;
+            let months = reports::monthly_summary(p, from, to).await?;
+            for u in currency::unconverted(p).await? {
+                eprintln!(
+                    "warning: {} entries in {} since {} have no exchange rate to the base currency and are left out",
+                    u.entries, u.currency, u.first_date
+                );
+            }
+            match format {
+                ReportFormat::Csv => io::stdout().write_all(&reports::monthly_csv(&months)?)?,
+                ReportFormat::Table => {
+                    let base_currency = currency::base_currency(p).await?;
+                    let amount = |a: &BigDecimal| {
+                        currency::format_amount(a.to_f64().unwrap_or_default(), &base_currency)
+                    };
+                    println!(
+                        "{:<8} {:>16} {:>16} {:>16} {:>12}",
+                        "month", "income", "expenses", "net savings", "savings rate"
+                    );
+                    for m in &months {
+                        println!(
+                            "{:<8} {:>16} {:>16} {:>16} {:>12}",
+                            m.month,
+                            amount(&m.income),
+                            amount(&m.expenses),
+                            amount(&m.net_savings),
+                            m.savings_rate
+                                .as_ref()
+                                .map(|r| format!("{}%", r))
+                                .unwrap_or_default()
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub async fn migrate(p: &Pool<Postgres>, command: MigrateCommand) -> anyhow::Result<()> {
+    match command {
+        MigrateCommand::Up => {
+            let count = migration::up(p).await?;
+            println!("{} migrations applied", count);
+        }
+        MigrateCommand::Down { n, yes } => {
+            let plan = migration::down_plan(p, n).await?;
+            let drops: Vec<&String> = plan.iter().flat_map(|r| &r.drops).collect();
+            if !drops.is_empty() && !yes {
+                for r in &plan {
+                    println!("{}", r.location);
+                    for d in &r.drops {
+                        println!("    {}", d);
+                    }
+                }
+                anyhow::bail!(
+                    "{} statements above drop tables or columns and the data in them, run again with --yes to revert",
+                    drops.len()
+                );
+            }
+            let count = migration::down(p, n).await?;
+            println!("{} migrations reverted", count);
+        }
+        MigrateCommand::Status => {
+            for s in migration::status(p).await? {
+                let mut notes = Vec::new();
+                if s.changed {
+                    notes.push("changed after it was applied");
+                }
+                if s.missing {
+                    notes.push("file is missing");
+                }
+                if !s.reversible && !s.missing {
+                    notes.push("no down file");
+                }
+                println!(
+                    "{:03} {:<40} {:<16} {}",
+                    s.version,
+                    s.name,
+                    s.applied_at
+                        .map(|a| a.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or("pending".to_string()),
+                    notes.join(", ")
+                );
+            }
+        }
+        MigrateCommand::New { name } => new_migration(&name)?,
+    }
+    Ok(())
+}
+
+/// `migrate new`, which does not need the database.
+pub fn new_migration(name: &str) -> anyhow::Result<()> {
+    let (up, down) = migration::new(name)?;
+    println!("created {}", up.display());
+    println!("created {}", down.display());
+    Ok(())
+}
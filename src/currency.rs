@@ -0,0 +1,378 @@
+use std::{str::FromStr, sync::OnceLock};
+
+use anyhow::{anyhow, bail, Context};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use sqlx::{Pool, Postgres};
+
+use crate::error::BadRequest;
+
+/// Saved on startup when no base currency was chosen or configured.
+pub const DEFAULT_BASE_CURRENCY: &str = "PLN";
+const BASE_CURRENCY_KEY: &str = "base_currency";
+
+pub const SOURCE_MANUAL: &str = "manual";
+pub const SOURCE_NBP: &str = "nbp";
+pub const SOURCE_ECB: &str = "ecb";
+
+/// Languages which write `12,50` instead of `12.50`.
+const DECIMAL_COMMA_LANGUAGES: &[&str] = &[
+    "bg", "cs", "da", "de", "el", "es", "et", "fi", "fr", "hr", "hu", "id", "it", "lt", "lv", "nb",
+    "nl", "nn", "no", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk", "vi",
+];
+static DECIMAL_COMMA: OnceLock<bool> = OnceLock::new();
+
+/// Precision of rates inverted from ECB files, which quote foreign currencies per euro.
+const INVERTED_RATE_SCALE: i64 = 10;
+
+/// ISO 4217 style code, three upper case letters.
+pub fn is_valid_code(code: &str) -> bool {
+    code.len() == 3 && code.chars().all(|c| c.is_ascii_uppercase())
+}
+
+pub fn symbol(code: &str) -> &str {
+    match code {
+        "PLN" => "zł",
+        "EUR" => "€",
+        "USD" => "$",
+        "GBP" => "£",
+        "CHF" => "Fr",
+        "CZK" => "Kč",
+        "JPY" => "¥",
+        "SEK" | "NOK" | "DKK" => "kr",
+        "UAH" => "₴",
+        "HUF" => "Ft",
+        code => code,
+    }
+}
+
+/// Chooses the decimal separator of `format_amount` from a language tag, e.g. `pl-PL`.
+/// Can be called once, before amounts are formatted.
+pub fn set_locale(locale: &str) {
+    let language = locale.split(['-', '_']).next().unwrap_or_default();
+    let _ = DECIMAL_COMMA.set(DECIMAL_COMMA_LANGUAGES.contains(&language));
+}
+
+/// Amount with two decimal places and the symbol of the currency, e.g. `12.50 zł` or `$12.50`.
+pub fn format_amount(amount: f64, code: &str) -> String {
+    let mut number = format!("{:.02}", amount.abs());
+    if DECIMAL_COMMA.get().copied().unwrap_or_default() {
+        number = number.replace('.', ",");
+    }
+    let sign = if amount < 0.0 { "-" } else { "" };
+    match code {
+        "USD" | "GBP" => format!("{}{}{}", sign, symbol(code), number),
+        _ => format!("{}{} {}", sign, number, symbol(code)),
+    }
+}
+
+pub async fn base_currency(p: &Pool<Postgres>) -> anyhow::Result<String> {
+    sqlx::query_scalar("SELECT value FROM setting WHERE key = $1")
+        .bind(BASE_CURRENCY_KEY)
+        .fetch_one(p)
+        .await
+        .context("base currency is not saved")
+}
+
+/// Saves `code`, or the default, as the base currency when none was chosen yet.
+/// Has to run before amounts are converted, `to_base_currency()` reads the saved
+/// value and has no default of its own.
+pub async fn init_base_currency(p: &Pool<Postgres>, code: Option<&str>) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO setting (key, value) VALUES ($1, $2) ON CONFLICT (key) DO NOTHING")
+        .bind(BASE_CURRENCY_KEY)
+        .bind(code.unwrap_or(DEFAULT_BASE_CURRENCY))
+        .execute(p)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_base_currency(p: &Pool<Postgres>, code: &str) -> anyhow::Result<()> {
+    if !is_valid_code(code) {
+        return Err(anyhow!(BadRequest(
+            "currency should be a three letter code, e.g. PLN".into()
+        )));
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO setting (key, value) VALUES ($1, $2)
+        ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value
+        "#,
+    )
+    .bind(BASE_CURRENCY_KEY)
+    .bind(code)
+    .execute(p)
+    .await?;
+    Ok(())
+}
+
+/// Entries in `currency` which have no exchange rate to the base currency on or
+/// before their accounting date, they are left out of amounts in the base currency.
+#[derive(Debug, sqlx::FromRow, serde::Serialize, utoipa::ToSchema)]
+pub struct Unconverted {
+    pub currency: String,
+    pub entries: i64,
+    pub first_date: NaiveDate,
+}
+
+pub async fn unconverted(p: &Pool<Postgres>) -> anyhow::Result<Vec<Unconverted>> {
+    Ok(sqlx::query_as(
+        r#"
+        SELECT currency, COUNT(*) AS entries, MIN(accounting_date) AS first_date
+        FROM entry
+        WHERE to_base_currency(amount, currency, accounting_date) IS NULL
+        GROUP BY currency
+        ORDER BY currency ASC
+        "#,
+    )
+    .fetch_all(p)
+    .await?)
+}
+
+/// One unit of `currency` is worth `rate` of `quote` on `date`.
+#[derive(Debug)]
+pub struct Rate {
+    pub currency: String,
+    pub quote: String,
+    pub date: NaiveDate,
+    pub rate: BigDecimal,
+}
+
+/// Parses an NBP table A archive (`data;1USD;100HUF;...`, rates in PLN) or the ECB
+/// reference rates history (`Date,USD,JPY,...`, rates per euro). Returns the rates
+/// and the name of the source.
+pub fn parse_rates(content: &str) -> anyhow::Result<(Vec<Rate>, &'static str)> {
+    let header = content
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('\u{feff}');
+    if header.to_lowercase().starts_with("data;") {
+        Ok((parse_nbp(content)?, SOURCE_NBP))
+    } else if header.starts_with("Date,") {
+        Ok((parse_ecb(content)?, SOURCE_ECB))
+    } else {
+        Err(anyhow!(BadRequest(
+            "unknown file format, expected an NBP (data;1USD;...) or ECB (Date,USD,...) CSV file"
+                .into()
+        )))
+    }
+}
+
+fn parse_nbp(content: &str) -> anyhow::Result<Vec<Rate>> {
+    let mut lines = content.lines();
+    // `1USD` or `100HUF`, other columns are table numbers
+    let columns: Vec<Option<(BigDecimal, String)>> = lines
+        .next()
+        .unwrap_or_default()
+        .split(';')
+        .map(|c| {
+            let c = c.trim();
+            let i = c.find(|ch: char| !ch.is_ascii_digit())?;
+            let (units, code) = c.split_at(i);
+            if !is_valid_code(code) {
+                return None;
+            }
+            Some((BigDecimal::from_str(units).ok()?, code.to_string()))
+        })
+        .collect();
+    if let Some((_, code)) = columns.iter().flatten().find(|(units, _)| units.is_zero()) {
+        bail!(BadRequest(format!(
+            "{}: number of units cannot be zero",
+            code
+        )));
+    }
+
+    let mut rates = Vec::new();
+    for line in lines {
+        let values: Vec<&str> = line.split(';').collect();
+        // the archive ends with rows of currency names and codes
+        let Ok(date) = NaiveDate::parse_from_str(values[0].trim(), "%Y%m%d") else {
+            continue;
+        };
+        for (value, column) in values.iter().zip(columns.iter()) {
+            let Some((units, code)) = column else {
+                continue;
+            };
+            let value = value.trim().replace(',', ".");
+            if value.is_empty() {
+                continue;
+            }
+            let rate = BigDecimal::from_str(&value)
+                .with_context(|| BadRequest(format!("{} rate on {}: {}", code, date, value)))?;
+            if rate <= BigDecimal::zero() {
+                bail!(BadRequest(format!(
+                    "{} rate on {} has to be greater than zero: {}",
+                    code, date, value
+                )));
+            }
+            rates.push(Rate {
+                currency: code.clone(),
+                quote: "PLN".to_string(),
+                date,
+                rate: rate / units,
+            });
+        }
+    }
+    Ok(rates)
+}
+
+fn parse_ecb(content: &str) -> anyhow::Result<Vec<Rate>> {
+    let mut lines = content.lines();
+    let codes: Vec<String> = lines
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(|c| c.trim().to_string())
+        .collect();
+
+    let mut rates = Vec::new();
+    for line in lines.filter(|l| !l.trim().is_empty()) {
+        let values: Vec<&str> = line.split(',').collect();
+        let date = NaiveDate::parse_from_str(values[0].trim(), "%Y-%m-%d")
+            .with_context(|| BadRequest(format!("invalid date: {}", values[0])))?;
+        for (value, code) in values.iter().zip(codes.iter()).skip(1) {
+            let value = value.trim();
+            if !is_valid_code(code) || value.is_empty() || value == "N/A" {
+                continue;
+            }
+            let per_euro = BigDecimal::from_str(value)
+                .with_context(|| BadRequest(format!("{} rate on {}: {}", code, date, value)))?;
+            if per_euro <= BigDecimal::zero() {
+                bail!(BadRequest(format!(
+                    "{} rate on {} has to be greater than zero: {}",
+                    code, date, value
+                )));
+            }
+            rates.push(Rate {
+                currency: code.clone(),
+                quote: "EUR".to_string(),
+                date,
+                rate: (BigDecimal::from(1) / per_euro).round(INVERTED_RATE_SCALE),
+            });
+        }
+    }
+    Ok(rates)
+}
+
+/// Inserts the rates, rates already stored for the same day are replaced.
+pub async fn save_rates(p: &Pool<Postgres>, rates: &[Rate], source: &str) -> anyhow::Result<u64> {
+    let mut tx = p.begin().await?;
+    let mut count = 0;
+    for r in rates {
+        count += sqlx::query(
+            r#"
+            INSERT INTO exchange_rate (currency, quote, rate_date, rate, source)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (currency, quote, rate_date) DO UPDATE
+            SET rate = EXCLUDED.rate, source = EXCLUDED.source
+            "#,
+        )
+        .bind(&r.currency)
+        .bind(&r.quote)
+        .bind(r.date)
+        .bind(&r.rate)
+        .bind(source)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(r: &Rate) -> (&str, &str, String, String) {
+        (
+            &r.currency,
+            &r.quote,
+            r.date.to_string(),
+            r.rate.normalized().to_string(),
+        )
+    }
+
+    #[test]
+    fn parses_nbp_archive() {
+        let content = "\u{feff}data;1USD;100HUF;nr tabeli\n\
+                       20240102;3,9432;1,1389;001/A/NBP/2024\n\
+                       20240103;3,9909;;002/A/NBP/2024\n\
+                       kod ISO;USD;HUF;\n\
+                       nazwa waluty;dolar amerykański;forint;\n";
+
+        let (rates, source) = parse_rates(content).unwrap();
+
+        assert_eq!(source, SOURCE_NBP);
+        assert_eq!(
+            rates.iter().map(rate).collect::<Vec<_>>(),
+            [
+                ("USD", "PLN", "2024-01-02".into(), "3.9432".into()),
+                ("HUF", "PLN", "2024-01-02".into(), "0.011389".into()),
+                ("USD", "PLN", "2024-01-03".into(), "3.9909".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_nbp_rate() {
+        let err = parse_rates("data;1USD\n20240102;abc\n").unwrap_err();
+
+        assert!(err.is::<BadRequest>());
+        assert_eq!(err.to_string(), "USD rate on 2024-01-02: abc");
+    }
+
+    #[test]
+    fn rejects_zero_units_and_non_positive_nbp_rates() {
+        let err = parse_rates("data;0USD\n20240102;3,9432\n").unwrap_err();
+        assert!(err.is::<BadRequest>());
+        assert_eq!(err.to_string(), "USD: number of units cannot be zero");
+
+        for rate in ["0", "-3,9432"] {
+            let err = parse_rates(&format!("data;1USD\n20240102;{}\n", rate)).unwrap_err();
+            assert!(err.is::<BadRequest>(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn parses_ecb_history_per_euro() {
+        let content = "Date,USD,JPY,CYP,\n\
+                       2024-01-03,1.0919,155.02,N/A,\n\
+                       2024-01-02,1.0956,,N/A,\n";
+
+        let (rates, source) = parse_rates(content).unwrap();
+
+        assert_eq!(source, SOURCE_ECB);
+        assert_eq!(
+            rates.iter().map(rate).collect::<Vec<_>>(),
+            [
+                ("USD", "EUR", "2024-01-03".into(), "0.9158347834".into()),
+                ("JPY", "EUR", "2024-01-03".into(), "0.0064507805".into()),
+                ("USD", "EUR", "2024-01-02".into(), "0.9127418766".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_ecb_date() {
+        let err = parse_rates("Date,USD\n03.01.2024,1.0919\n").unwrap_err();
+
+        assert!(err.is::<BadRequest>());
+        assert_eq!(err.to_string(), "invalid date: 03.01.2024");
+    }
+
+    #[test]
+    fn rejects_non_positive_ecb_rates() {
+        for rate in ["0", "-1.0919"] {
+            let err = parse_rates(&format!("Date,USD\n2024-01-03,{}\n", rate)).unwrap_err();
+            assert!(err.is::<BadRequest>(), "{}", rate);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        assert!(parse_rates("currency,rate\nUSD,4\n")
+            .unwrap_err()
+            .is::<BadRequest>());
+    }
+}
@@ -0,0 +1,197 @@
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::error::BadRequest;
+
+/// Default distance in days between two entries which can still be the same transaction.
+pub const DEFAULT_MAX_DAYS: i32 = 3;
+/// Maximal distance which can be requested, wider windows match unrelated payments.
+pub const MAX_DAYS: i32 = 31;
+/// Minimal trigram similarity (0..1) of title or counterparty.
+pub const MIN_SIMILARITY: f32 = 0.3;
+
+/// Two entries on the same account with the same amount which look like one transaction.
+#[derive(sqlx::FromRow, Serialize)]
+pub struct Candidate {
+    pub a_id: Uuid,
+    pub a_accounting_date: NaiveDate,
+    pub a_sender_or_receiver: String,
+    pub a_title: String,
+    pub a_reference_number: String,
+    pub a_manual: bool,
+    pub b_id: Uuid,
+    pub b_accounting_date: NaiveDate,
+    pub b_sender_or_receiver: String,
+    pub b_title: String,
+    pub b_reference_number: String,
+    pub b_manual: bool,
+    pub source_account: String,
+    pub amount: BigDecimal,
+    pub currency: String,
+    pub days_apart: i32,
+    pub similarity: f32,
+}
+
+/// Finds likely duplicates: same account, amount and currency, accounting dates at
+/// most `max_days` apart and a similar title or counterparty. Pairs which were
+/// dismissed are skipped.
+pub async fn find(p: &Pool<Postgres>, max_days: i32) -> anyhow::Result<Vec<Candidate>> {
+    let candidates = sqlx::query_as::<_, Candidate>(
+        r#"
+        SELECT *
+        FROM (
+            SELECT
+                a.id AS a_id,
+                a.accounting_date AS a_accounting_date,
+                a.sender_or_receiver AS a_sender_or_receiver,
+                a.title AS a_title,
+                a.reference_number AS a_reference_number,
+                a.manual AS a_manual,
+                b.id AS b_id,
+                b.accounting_date AS b_accounting_date,
+                b.sender_or_receiver AS b_sender_or_receiver,
+                b.title AS b_title,
+                b.reference_number AS b_reference_number,
+                b.manual AS b_manual,
+                a.source_account,
+                a.amount,
+                a.currency,
+                ABS(a.accounting_date - b.accounting_date) AS days_apart,
+                GREATEST(
+                    similarity(LOWER(a.title), LOWER(b.title)),
+                    similarity(LOWER(a.sender_or_receiver), LOWER(b.sender_or_receiver)),
+                    similarity(
+                        LOWER(a.title || ' ' || a.sender_or_receiver),
+                        LOWER(b.title || ' ' || b.sender_or_receiver)
+                    )
+                ) AS similarity
+            FROM entry a
+            JOIN entry b ON
+                b.source_account = a.source_account AND
+                b.amount = a.amount AND
+                b.currency = a.currency AND
+                a.id < b.id AND
+                ABS(a.accounting_date - b.accounting_date) <= $1
+            WHERE NOT EXISTS (
+                SELECT 1 FROM duplicate_dismissal d
+                WHERE d.entry_a = a.id AND d.entry_b = b.id
+            )
+        ) c
+        WHERE c.similarity >= $2
+        ORDER BY c.similarity DESC, c.days_apart ASC, c.a_accounting_date DESC
+        "#,
+    )
+    .bind(max_days)
+    .bind(MIN_SIMILARITY)
+    .fetch_all(p)
+    .await?;
+
+    Ok(candidates)
+}
+
+/// Marks the pair as not a duplicate, so `find` no longer reports it.
+pub async fn dismiss(p: &Pool<Postgres>, a: Uuid, b: Uuid) -> anyhow::Result<()> {
+    let (a, b) = if a < b { (a, b) } else { (b, a) };
+    sqlx::query(
+        r#"
+        INSERT INTO duplicate_dismissal (entry_a, entry_b)
+        VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(a)
+    .bind(b)
+    .execute(p)
+    .await?;
+    Ok(())
+}
+
+/// Merges `remove` into `keep` and deletes it. The kept entry gets the tags of
+/// both, the note of the removed one when it has none, and its attachments.
+/// The merge is recorded in the audit log of the kept entry and the reference
+/// of the removed one is remembered, so importing it again is skipped.
+pub async fn merge(p: &Pool<Postgres>, keep: Uuid, remove: Uuid) -> anyhow::Result<()> {
+    if keep == remove {
+        anyhow::bail!(BadRequest("cannot merge an entry with itself".into()));
+    }
+
+    let mut tx = p.begin().await?;
+
+    let removed: Option<(String, String, NaiveDate)> = sqlx::query_as(
+        r#"
+        SELECT r.reference_number, r.title, r.accounting_date
+        FROM entry r
+        JOIN entry k ON k.id = $1
+        WHERE r.id = $2 AND r.source_account = k.source_account AND r.amount = k.amount
+        FOR UPDATE
+        "#,
+    )
+    .bind(keep)
+    .bind(remove)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((reference_number, title, accounting_date)) = removed else {
+        anyhow::bail!(BadRequest(
+            "entries do not exist or have a different account or amount".into()
+        ));
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE entry k
+        SET
+            tags = ARRAY(SELECT DISTINCT UNNEST(k.tags || r.tags) ORDER BY 1),
+            note = CASE WHEN k.note = '' THEN r.note ELSE k.note END
+        FROM entry r
+        WHERE k.id = $1 AND r.id = $2
+        "#,
+    )
+    .bind(keep)
+    .bind(remove)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE attachment SET entry_id = $1 WHERE entry_id = $2")
+        .bind(keep)
+        .bind(remove)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO entry_audit (entry_id, field, old_value, new_value) VALUES ($1, 'merged', $2, '')",
+    )
+    .bind(keep)
+    .bind(format!("{} {} ({})", accounting_date, title, reference_number))
+    .execute(&mut *tx)
+    .await?;
+
+    // the import skips this reference, otherwise the next upload brings the entry back
+    sqlx::query(
+        r#"
+        INSERT INTO merged_reference (reference_number, entry_id)
+        VALUES ($1, $2)
+        ON CONFLICT (reference_number) DO UPDATE SET entry_id = EXCLUDED.entry_id
+        "#,
+    )
+    .bind(&reference_number)
+    .bind(keep)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE merged_reference SET entry_id = $1 WHERE entry_id = $2")
+        .bind(keep)
+        .bind(remove)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM entry WHERE id = $1")
+        .bind(remove)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(())
+}
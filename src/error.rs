@@ -0,0 +1,29 @@
+//! Errors caused by the input of a request. The web server answers them with their
+//! status code and message, any other error is internal and its details are only
+//! logged, see `front::accounts::ErrorKind`.
+
+use std::fmt;
+
+/// The input is invalid, e.g. a form field or an uploaded file.
+#[derive(Debug)]
+pub struct BadRequest(pub String);
+
+/// The input refers to something which does not exist.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl fmt::Display for BadRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BadRequest {}
+
+impl fmt::Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
@@ -0,0 +1,107 @@
+use std::io;
+
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use sqlx::{Pool, Postgres};
+
+/// Formats entries can be exported to.
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum Format {
+    /// `,` separated with a header, ISO dates and tags joined with `|`
+    #[default]
+    Csv,
+}
+
+/// Writes entries with an accounting date between `from` and `to` (both inclusive)
+/// and returns their number.
+pub async fn entries(
+    p: &Pool<Postgres>,
+    format: Format,
+    from: NaiveDate,
+    to: NaiveDate,
+    w: impl io::Write,
+) -> anyhow::Result<usize> {
+    #[derive(sqlx::FromRow)]
+    struct Record {
+        accounting_date: NaiveDate,
+        currency_date: NaiveDate,
+        sender_or_receiver: String,
+        source_account: String,
+        destination_account: String,
+        title: String,
+        amount: BigDecimal,
+        currency: String,
+        category: String,
+        operation_type: String,
+        reference_number: String,
+        note: String,
+        tags: Vec<String>,
+    }
+
+    let entries = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            accounting_date,
+            currency_date,
+            sender_or_receiver,
+            source_account,
+            destination_account,
+            title,
+            amount,
+            currency,
+            category,
+            operation_type,
+            reference_number,
+            note,
+            tags
+        FROM entry
+        WHERE accounting_date BETWEEN $1 AND $2
+        ORDER BY accounting_date ASC, reference_number ASC
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(p)
+    .await?;
+
+    match format {
+        Format::Csv => {
+            let mut wtr = csv::Writer::from_writer(w);
+            wtr.write_record([
+                "accounting_date",
+                "currency_date",
+                "sender_or_receiver",
+                "source_account",
+                "destination_account",
+                "title",
+                "amount",
+                "currency",
+                "category",
+                "operation_type",
+                "reference_number",
+                "note",
+                "tags",
+            ])?;
+            for e in &entries {
+                wtr.write_record([
+                    e.accounting_date.to_string(),
+                    e.currency_date.to_string(),
+                    e.sender_or_receiver.clone(),
+                    e.source_account.clone(),
+                    e.destination_account.clone(),
+                    e.title.clone(),
+                    e.amount.round(2).to_string(),
+                    e.currency.clone(),
+                    e.category.clone(),
+                    e.operation_type.clone(),
+                    e.reference_number.clone(),
+                    e.note.clone(),
+                    e.tags.join("|"),
+                ])?;
+            }
+            wtr.flush()?;
+        }
+    }
+
+    Ok(entries.len())
+}
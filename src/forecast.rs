@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::BigDecimal;
+use chrono::{Months, NaiveDate};
+use sqlx::{Pool, Postgres};
+use uuid::Uuid;
+
+use crate::recurring::Period;
+
+pub const MIN_MONTHS: u32 = 3;
+pub const MAX_MONTHS: u32 = 12;
+
+/// Recurring income or expense expected on an account.
+#[derive(Debug, Clone)]
+pub struct Flow {
+    pub amount: BigDecimal,
+    pub period: Period,
+    pub next_date: NaiveDate,
+}
+
+#[derive(Debug)]
+pub struct AccountForecast {
+    pub name: String,
+    pub opening_balance: BigDecimal,
+    /// Balance at the end of each day, starting from today.
+    pub balances: Vec<(NaiveDate, BigDecimal)>,
+}
+
+impl AccountForecast {
+    /// First day when the balance drops below `threshold`.
+    pub fn first_below(&self, threshold: &BigDecimal) -> Option<&(NaiveDate, BigDecimal)> {
+        self.balances.iter().find(|(_, b)| b < threshold)
+    }
+
+    pub fn lowest(&self) -> Option<&(NaiveDate, BigDecimal)> {
+        self.balances.iter().min_by(|a, b| a.1.cmp(&b.1))
+    }
+}
+
+/// Projects balance day by day. Flows start with their first occurrence on or
+/// after `from`, earlier ones are already in the opening balance or are missed.
+pub fn project(
+    opening_balance: &BigDecimal,
+    flows: &[Flow],
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Vec<(NaiveDate, BigDecimal)> {
+    let mut changes: BTreeMap<NaiveDate, BigDecimal> = BTreeMap::new();
+    for f in flows {
+        let mut date = f.next_date;
+        while date <= to {
+            if date >= from {
+                *changes.entry(date).or_default() += &f.amount;
+            }
+            match f.period.next(date) {
+                Some(next) => date = next,
+                None => break,
+            }
+        }
+    }
+
+    let mut balance = opening_balance.clone();
+    from.iter_days()
+        .take_while(|d| *d <= to)
+        .map(|d| {
+            if let Some(change) = changes.get(&d) {
+                balance += change;
+            }
+            (d, balance.clone())
+        })
+        .collect()
+}
+
+/// Forecast for every account using current balances, detected subscriptions
+/// which did not stop and user defined planned transactions. Amounts are in the
+/// base currency, balances and subscriptions are converted with the last known
+/// rate, planned transactions are entered in the base currency.
+pub async fn forecast(p: &Pool<Postgres>, months: u32) -> anyhow::Result<Vec<AccountForecast>> {
+    let months = months.clamp(MIN_MONTHS, MAX_MONTHS);
+
+    #[derive(sqlx::FromRow)]
+    struct Account {
+        id: Uuid,
+        name: String,
+        balance: BigDecimal,
+    }
+
+    let accounts: Vec<Account> = sqlx::query_as(
+        r#"
+        SELECT
+            a.id,
+            a.name,
+            (
+                SELECT COALESCE(SUM(to_base_currency(amount, currency, CURRENT_DATE)), 0)
+                FROM entry
+                WHERE source_account IN (
+                    SELECT reference FROM account_reference WHERE account_id = a.id
+                )
+            ) AS balance
+        FROM account a
+        ORDER BY a.name ASC
+        "#,
+    )
+    .fetch_all(p)
+    .await?;
+
+    #[derive(sqlx::FromRow)]
+    struct Record {
+        account_id: Uuid,
+        amount: BigDecimal,
+        period: String,
+        next_date: NaiveDate,
+    }
+
+    let records: Vec<Record> = sqlx::query_as(
+        r#"
+        SELECT
+            r.account_id,
+            to_base_currency(s.last_amount, s.currency, CURRENT_DATE) AS amount,
+            s.period,
+            s.next_expected_date AS next_date
+        FROM subscription s
+        JOIN account_reference r ON r.reference = s.source_account
+        WHERE
+            NOT s.stopped AND
+            to_base_currency(s.last_amount, s.currency, CURRENT_DATE) IS NOT NULL
+        UNION ALL
+        SELECT
+            account_id,
+            amount,
+            period,
+            next_date
+        FROM planned_transaction
+        "#,
+    )
+    .fetch_all(p)
+    .await?;
+
+    let mut flows: BTreeMap<Uuid, Vec<Flow>> = BTreeMap::new();
+    for r in records {
+        flows.entry(r.account_id).or_default().push(Flow {
+            amount: r.amount,
+            period: Period::try_from(r.period.as_str())?,
+            next_date: r.next_date,
+        });
+    }
+
+    let from = chrono::Local::now().date_naive();
+    let to = from
+        .checked_add_months(Months::new(months))
+        .ok_or(anyhow::anyhow!("forecast end date is out of range"))?;
+
+    Ok(accounts
+        .into_iter()
+        .map(|a| AccountForecast {
+            balances: project(
+                &a.balance,
+                flows.get(&a.id).map(Vec::as_slice).unwrap_or_default(),
+                from,
+                to,
+            ),
+            name: a.name,
+            opening_balance: a.balance,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn flow(amount: i64, period: Period, next_date: &str) -> Flow {
+        Flow {
+            amount: BigDecimal::from(amount),
+            period,
+            next_date: date(next_date),
+        }
+    }
+
+    fn balance_on<'a>(balances: &'a [(NaiveDate, BigDecimal)], d: &str) -> &'a BigDecimal {
+        &balances.iter().find(|(day, _)| *day == date(d)).unwrap().1
+    }
+
+    #[test]
+    fn projects_every_day_of_the_range() {
+        let balances = project(
+            &BigDecimal::from(100),
+            &[],
+            date("2024-01-30"),
+            date("2024-02-02"),
+        );
+
+        assert_eq!(
+            balances,
+            [
+                (date("2024-01-30"), BigDecimal::from(100)),
+                (date("2024-01-31"), BigDecimal::from(100)),
+                (date("2024-02-01"), BigDecimal::from(100)),
+                (date("2024-02-02"), BigDecimal::from(100)),
+            ]
+        );
+    }
+
+    #[test]
+    fn books_flows_on_their_dates() {
+        let balances = project(
+            &BigDecimal::from(1000),
+            &[
+                flow(3000, Period::Monthly, "2024-01-10"),
+                flow(-50, Period::Weekly, "2024-01-03"),
+                flow(-200, Period::Yearly, "2024-02-15"),
+            ],
+            date("2024-01-01"),
+            date("2024-02-29"),
+        );
+
+        assert_eq!(balance_on(&balances, "2024-01-02"), &BigDecimal::from(1000));
+        assert_eq!(balance_on(&balances, "2024-01-03"), &BigDecimal::from(950));
+        assert_eq!(balance_on(&balances, "2024-01-10"), &BigDecimal::from(3900));
+        // 9 weekly payments, 2 salaries and the yearly payment
+        assert_eq!(balance_on(&balances, "2024-02-29"), &BigDecimal::from(6350));
+    }
+
+    #[test]
+    fn skips_occurrences_before_the_first_day() {
+        let balances = project(
+            &BigDecimal::from(500),
+            &[
+                flow(-100, Period::Monthly, "2023-10-20"),
+                flow(-30, Period::Weekly, "2024-01-01"),
+            ],
+            date("2024-01-05"),
+            date("2024-01-25"),
+        );
+
+        assert_eq!(balance_on(&balances, "2024-01-05"), &BigDecimal::from(500));
+        assert_eq!(balance_on(&balances, "2024-01-08"), &BigDecimal::from(470));
+        assert_eq!(balance_on(&balances, "2024-01-20"), &BigDecimal::from(340));
+    }
+
+    #[test]
+    fn finds_first_day_below_threshold_and_lowest_balance() {
+        let forecast = AccountForecast {
+            name: "main".to_string(),
+            opening_balance: BigDecimal::from(300),
+            balances: project(
+                &BigDecimal::from(300),
+                &[
+                    flow(-250, Period::Weekly, "2024-01-02"),
+                    flow(1000, Period::Monthly, "2024-01-10"),
+                ],
+                date("2024-01-01"),
+                date("2024-01-20"),
+            ),
+        };
+
+        assert_eq!(
+            forecast.first_below(&BigDecimal::from(0)),
+            Some(&(date("2024-01-09"), BigDecimal::from(-200)))
+        );
+        assert_eq!(
+            forecast.lowest(),
+            Some(&(date("2024-01-09"), BigDecimal::from(-200)))
+        );
+    }
+}
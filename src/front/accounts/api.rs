@@ -19,10 +19,6 @@ async fn get(
     State(s): State<AppState>,
     Query(q): Query<table::Query>,
 ) -> Result<Response, AppMessage> {
-    let q = q.noramlize();
-
-    dbg!(q.clone());
-
     #[derive(sqlx::FromRow, Default, Serialize)]
     struct Record {
         id: Uuid,
@@ -31,6 +27,10 @@ async fn get(
         reference: String,
     }
 
+    let q = q
+        .noramlize::<Record>()
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
     let count: i64 = sqlx::query(
         r#"
         SELECT count(*)
@@ -43,7 +43,7 @@ async fn get(
     .try_get(0)
     .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
 
-    let records = sqlx::query_as::<_, Record>(
+    let records = sqlx::query_as::<_, Record>(&format!(
         r#"
         SELECT 
             id,
@@ -52,10 +52,12 @@ async fn get(
             reference
         FROM account 
         JOIN account_reference ON id = account_id
+        ORDER BY {}
         LIMIT $1
         OFFSET $2 
         "#,
-    )
+        q.order_by("name ASC, id ASC, reference ASC")
+    ))
     .bind(q.limit())
     .bind(q.offset())
     .fetch_all(&s.p)
@@ -63,7 +65,13 @@ async fn get(
     .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
 
     let table = table::TableComponent::<Record>::new(records, count, "/api/accounts", q)
-        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?
+        .with_columns(&["name", "reference", "id", "account_id"])
+        .with_label("name", "Name")
+        .with_label("reference", "Reference")
+        .with_label("id", "ID")
+        .with_label("account_id", "Account ID")
+        .with_target("accounts-table");
 
     #[derive(Serialize)]
     struct Ctx {
@@ -1,66 +1,129 @@
 pub mod api;
 
-use std::any::Any;
-
-use super::{
-    components::{self, table::TableComponent},
-    AppState,
-};
+use super::AppState;
+use crate::error::{BadRequest, NotFound};
 use anyhow::anyhow;
 use axum::{
     extract::State,
+    http::{HeaderValue, StatusCode},
     response::{IntoResponse, Response},
-    Router,
+    Form, Router,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// Response of a handler: an info notification, or an error shown as a page or a
+/// notification with the status code of the error, see `ErrorKind`.
 pub struct AppMessage(Response);
 
-impl AppMessage {
-    pub fn new_info_notification(msg: impl AsRef<str>, s: &AppState) -> AppMessage {
-        #[derive(Serialize, Default)]
-        struct Ctx {
-            info: Option<String>,
-            warn: Option<String>,
-            error: Option<String>,
-        }
+/// How an error is reported to the client.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    BadRequest,
+    NotFound,
+    Internal,
+}
 
-        let mut ctx = Ctx::default();
-        ctx.info = Some(msg.as_ref().to_string());
+impl ErrorKind {
+    /// Errors marked with `BadRequest` or `NotFound` by the code checking the input
+    /// and invalid values and violated constraints are reported to the client, missing
+    /// rows are not found. Anything else is internal.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if err.is::<BadRequest>() {
+            return ErrorKind::BadRequest;
+        }
+        if err.is::<NotFound>() {
+            return ErrorKind::NotFound;
+        }
+        match err.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => ErrorKind::NotFound,
+            Some(sqlx::Error::Database(err))
+                if err
+                    .code()
+                    .is_some_and(|c| c.starts_with("22") || c.starts_with("23")) =>
+            {
+                ErrorKind::BadRequest
+            }
+            _ => ErrorKind::Internal,
+        }
+    }
 
-        Self(s.t.render("base.notification.hbs", &ctx).unwrap())
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ErrorKind::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            ErrorKind::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
     }
 
-    pub fn new_error_notification(msg: anyhow::Error, s: &AppState) -> AppMessage {
-        #[derive(Serialize, Default)]
-        struct Ctx {
-            info: Option<String>,
-            warn: Option<String>,
-            error: Option<String>,
+    /// Text shown to the client, details of internal errors are only logged.
+    pub fn message(&self, err: &anyhow::Error) -> String {
+        match self {
+            ErrorKind::Internal => "something went wrong, try again later".to_string(),
+            _ => err.to_string(),
         }
+    }
+}
+
+/// Message of an error response, logged together with the request by `front::log_errors`.
+#[derive(Clone)]
+pub struct ErrorMessage(pub String);
+
+impl AppMessage {
+    pub fn new_info_notification(msg: impl AsRef<str>, s: &AppState) -> AppMessage {
+        let ctx = Ctx {
+            info: Some(msg.as_ref().to_string()),
+            ..Default::default()
+        };
 
-        let mut ctx = Ctx::default();
-        ctx.error = Some(msg.to_string());
+        Self(render(s, "base.notification.hbs", &ctx))
+    }
 
-        Self(s.t.render("base.notification.hbs", &ctx).unwrap())
+    pub fn new_error_notification(msg: anyhow::Error, s: &AppState) -> AppMessage {
+        let kind = ErrorKind::of(&msg);
+        Self::error("base.notification.hbs", kind, msg, s)
     }
 
     pub fn new_error(msg: anyhow::Error, s: &AppState) -> AppMessage {
-        #[derive(Serialize, Default)]
-        struct Ctx {
-            info: Option<String>,
-            warn: Option<String>,
-            error: Option<String>,
-        }
-
-        let mut ctx = Ctx::default();
-        ctx.error = Some(msg.to_string());
+        let kind = ErrorKind::of(&msg);
+        Self::error("error.get.hbs", kind, msg, s)
+    }
 
-        Self(s.t.render("error.get.hbs", &ctx).unwrap())
+    fn error(template: &str, kind: ErrorKind, msg: anyhow::Error, s: &AppState) -> AppMessage {
+        let ctx = Ctx {
+            error: Some(kind.message(&msg)),
+            ..Default::default()
+        };
+
+        let mut res = render(s, template, &ctx);
+        *res.status_mut() = kind.status();
+        res.extensions_mut()
+            .insert(ErrorMessage(format!("{:#}", msg)));
+        Self(res)
     }
 }
 
+#[derive(Serialize, Default)]
+struct Ctx {
+    info: Option<String>,
+    warn: Option<String>,
+    error: Option<String>,
+}
+
+/// Plain text when the template cannot be rendered, the message is not lost.
+fn render(s: &AppState, template: &str, ctx: &Ctx) -> Response {
+    s.t.render(template, ctx).unwrap_or_else(|err| {
+        log::error!("cannot render {}: {:#}", template, err);
+        let text = [&ctx.info, &ctx.warn, &ctx.error]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        text.into_response()
+    })
+}
+
 impl IntoResponse for AppMessage {
     fn into_response(self) -> Response {
         self.0
@@ -81,7 +144,68 @@ async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
     Ok(res)
 }
 
+#[derive(Deserialize)]
+struct AccountForm {
+    name: String,
+    /// Account number used in bank files, generated for cash accounts when empty.
+    reference: String,
+}
+
 #[axum::debug_handler]
-async fn post(State(s): State<AppState>) -> Result<Response, AppMessage> {
-    todo!()
+async fn post(
+    State(s): State<AppState>,
+    Form(f): Form<AccountForm>,
+) -> Result<Response, AppMessage> {
+    if f.name.trim().is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("name cannot be empty".into())),
+            &s,
+        ));
+    }
+    let reference = f.reference.replace(' ', "");
+
+    let mut tx =
+        s.p.begin()
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let used: bool =
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM account_reference WHERE reference = $1)")
+            .bind(&reference)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    if used {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(format!("account {} already exists", reference))),
+            &s,
+        ));
+    }
+
+    let id: Uuid = sqlx::query_scalar("INSERT INTO account (name) VALUES ($1) RETURNING id")
+        .bind(f.name.trim())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO account_reference (account_id, reference)
+        VALUES ($1, COALESCE(NULLIF($2, ''), 'CASH-' || gen_random_uuid()))
+        "#,
+    )
+    .bind(id)
+    .bind(&reference)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    tx.commit()
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let mut ret = AppMessage::new_info_notification("account added", &s).into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("accounts-updated"));
+    Ok(ret)
 }
@@ -0,0 +1,110 @@
+use serde::Serialize;
+
+const WIDTH: f64 = 1000.0;
+const HEIGHT: f64 = 300.0;
+const MAX_X_LABELS: usize = 6;
+const COLORS: [&str; 6] = [
+    "#00d1b2", "#485fc7", "#ffb70f", "#f14668", "#3e8ed0", "#48c78e",
+];
+
+#[derive(Serialize, Default)]
+pub struct Line {
+    name: String,
+    color: &'static str,
+    points: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct Guide {
+    label: String,
+    y: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct Label {
+    text: String,
+    x: String,
+}
+
+/// Server side rendered SVG chart with one line per series,
+/// rendered by `component.line_chart.hbs`.
+#[derive(Serialize, Default)]
+pub struct LineChart {
+    view_box: String,
+    width: f64,
+    height: f64,
+    lines: Vec<Line>,
+    guides: Vec<Guide>,
+    x_labels: Vec<Label>,
+    max_label: String,
+    min_label: String,
+}
+
+impl LineChart {
+    /// All series should have the same length as `x_labels`.
+    /// Guides are horizontal lines, e.g. zero or a threshold.
+    pub fn new(
+        series: Vec<(String, Vec<f64>)>,
+        x_labels: Vec<String>,
+        guides: Vec<(String, f64)>,
+    ) -> Self {
+        let values = series
+            .iter()
+            .flat_map(|(_, v)| v.iter())
+            .chain(guides.iter().map(|(_, v)| v));
+        let (mut min, mut max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| {
+            (min.min(*v), max.max(*v))
+        });
+        if min > max {
+            (min, max) = (0.0, 0.0);
+        }
+        if min == max {
+            (min, max) = (min - 1.0, max + 1.0);
+        }
+
+        let points = x_labels.len().max(2) - 1;
+        let x = |i: usize| i as f64 / points as f64 * WIDTH;
+        let y = |v: f64| HEIGHT - (v - min) / (max - min) * HEIGHT;
+
+        let step = x_labels.len().div_ceil(MAX_X_LABELS).max(1);
+
+        Self {
+            // room for value labels on the left and date labels at the bottom
+            view_box: format!("-80 -20 {} {}", WIDTH + 100.0, HEIGHT + 50.0),
+            width: WIDTH,
+            height: HEIGHT,
+            lines: series
+                .into_iter()
+                .enumerate()
+                .map(|(i, (name, values))| Line {
+                    name,
+                    color: COLORS[i % COLORS.len()],
+                    points: values
+                        .iter()
+                        .enumerate()
+                        .map(|(i, v)| format!("{:.1},{:.1}", x(i), y(*v)))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                })
+                .collect(),
+            guides: guides
+                .into_iter()
+                .map(|(label, v)| Guide {
+                    label,
+                    y: format!("{:.1}", y(v)),
+                })
+                .collect(),
+            x_labels: x_labels
+                .into_iter()
+                .enumerate()
+                .step_by(step)
+                .map(|(i, text)| Label {
+                    text,
+                    x: format!("{:.1}", x(i)),
+                })
+                .collect(),
+            max_label: format!("{:.02}", max),
+            min_label: format!("{:.02}", min),
+        }
+    }
+}
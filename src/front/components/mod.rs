@@ -1 +1,3 @@
+pub mod line_chart;
+pub mod sankey;
 pub mod table;
@@ -0,0 +1,169 @@
+use serde::Serialize;
+
+const WIDTH: f64 = 1000.0;
+const HEIGHT: f64 = 500.0;
+const NODE_WIDTH: f64 = 16.0;
+const NODE_PADDING: f64 = 12.0;
+const LABEL_MARGIN: f64 = 6.0;
+const COLORS: [&str; 4] = ["#48c78e", "#3e8ed0", "#f14668", "#ffb70f"];
+
+/// Money moving from a node in one column to a node in another column.
+/// Nodes are identified by column index and label.
+pub struct Flow {
+    pub from: (usize, String),
+    pub to: (usize, String),
+    pub value: f64,
+}
+
+#[derive(Serialize)]
+pub struct Node {
+    label: String,
+    value: String,
+    x: String,
+    y: String,
+    width: String,
+    height: String,
+    color: &'static str,
+    label_x: String,
+    label_y: String,
+    label_anchor: &'static str,
+}
+
+#[derive(Serialize)]
+pub struct Link {
+    path: String,
+    width: String,
+    color: &'static str,
+    title: String,
+}
+
+/// Server side rendered SVG Sankey diagram, rendered by `component.sankey.hbs`.
+#[derive(Serialize, Default)]
+pub struct Sankey {
+    view_box: String,
+    nodes: Vec<Node>,
+    links: Vec<Link>,
+}
+
+impl Sankey {
+    /// Flows with non positive value are skipped. Nodes keep the order in which
+    /// they first appear in `flows`.
+    pub fn new(columns: usize, flows: Vec<Flow>) -> Self {
+        let flows: Vec<Flow> = flows.into_iter().filter(|f| f.value > 0.0).collect();
+
+        let mut keys: Vec<&(usize, String)> = Vec::new();
+        for f in &flows {
+            for k in [&f.from, &f.to] {
+                if !keys.contains(&k) {
+                    keys.push(k);
+                }
+            }
+        }
+        let index = |k: &(usize, String)| keys.iter().position(|n| *n == k).unwrap_or(0);
+
+        let mut inflow = vec![0.0; keys.len()];
+        let mut outflow = vec![0.0; keys.len()];
+        for f in &flows {
+            outflow[index(&f.from)] += f.value;
+            inflow[index(&f.to)] += f.value;
+        }
+        let values: Vec<f64> = inflow
+            .iter()
+            .zip(outflow.iter())
+            .map(|(i, o)| i.max(*o))
+            .collect();
+
+        // one scale for all columns so the same amount has the same height everywhere
+        let scale = (0..columns)
+            .filter_map(|c| {
+                let column: Vec<f64> = keys
+                    .iter()
+                    .zip(values.iter())
+                    .filter(|(k, _)| k.0 == c)
+                    .map(|(_, v)| *v)
+                    .collect();
+                let total: f64 = column.iter().sum();
+                (total > 0.0).then(|| {
+                    (HEIGHT - NODE_PADDING * (column.len() as f64 - 1.0)).max(HEIGHT / 2.0) / total
+                })
+            })
+            .fold(f64::MAX, f64::min);
+        if keys.is_empty() {
+            return Self::default();
+        }
+
+        let column_x = |c: usize| match columns {
+            0 | 1 => 0.0,
+            _ => c as f64 * (WIDTH - NODE_WIDTH) / (columns - 1) as f64,
+        };
+
+        let mut cursor = vec![0.0; columns];
+        let mut positions = Vec::new();
+        for (k, v) in keys.iter().zip(values.iter()) {
+            positions.push((column_x(k.0), cursor[k.0]));
+            cursor[k.0] += v * scale + NODE_PADDING;
+        }
+
+        let nodes = keys
+            .iter()
+            .zip(values.iter())
+            .zip(positions.iter())
+            .map(|((k, v), (x, y))| {
+                let last_column = k.0 + 1 == columns;
+                Node {
+                    label: k.1.clone(),
+                    value: format!("{:.02}", v),
+                    x: format!("{:.1}", x),
+                    y: format!("{:.1}", y),
+                    width: format!("{:.1}", NODE_WIDTH),
+                    height: format!("{:.1}", (v * scale).max(1.0)),
+                    color: COLORS[k.0 % COLORS.len()],
+                    label_x: if last_column {
+                        format!("{:.1}", x - LABEL_MARGIN)
+                    } else {
+                        format!("{:.1}", x + NODE_WIDTH + LABEL_MARGIN)
+                    },
+                    label_y: format!("{:.1}", y + v * scale / 2.0),
+                    label_anchor: if last_column { "end" } else { "start" },
+                }
+            })
+            .collect();
+
+        let mut out_cursor: Vec<f64> = positions.iter().map(|(_, y)| *y).collect();
+        let mut in_cursor = out_cursor.clone();
+        let links = flows
+            .iter()
+            .map(|f| {
+                let (from, to) = (index(&f.from), index(&f.to));
+                let width = f.value * scale;
+                let y0 = out_cursor[from] + width / 2.0;
+                let y1 = in_cursor[to] + width / 2.0;
+                out_cursor[from] += width;
+                in_cursor[to] += width;
+                let x0 = positions[from].0 + NODE_WIDTH;
+                let x1 = positions[to].0;
+                let xm = (x0 + x1) / 2.0;
+                Link {
+                    path: format!(
+                        "M{:.1},{:.1} C{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}",
+                        x0, y0, xm, y0, xm, y1, x1, y1
+                    ),
+                    width: format!("{:.1}", width.max(1.0)),
+                    color: COLORS[f.from.0 % COLORS.len()],
+                    title: format!("{} → {}: {:.02}", f.from.1, f.to.1, f.value),
+                }
+            })
+            .collect();
+
+        Self {
+            // room for labels on both sides
+            view_box: format!(
+                "-10 -10 {} {}",
+                WIDTH + 20.0,
+                cursor.iter().fold(HEIGHT, |a, b| a.max(*b)) + 20.0
+            ),
+            nodes,
+            links,
+        }
+    }
+}
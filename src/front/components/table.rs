@@ -1,11 +1,16 @@
-use std::ops::Sub;
+use std::{collections::HashMap, ops::Sub};
 
+use crate::{currency, error::BadRequest};
 use anyhow::anyhow;
-use bigdecimal::num_traits::CheckedMul;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use utoipa::IntoParams;
 
 pub type Url = String;
 
+/// Turns a serialized field into the text of a cell, the second argument is the whole row.
+pub type Formatter = fn(&Value, &Map<String, Value>) -> String;
+
 const DEFAULT_ENTRIES_PER_PAGE: u32 = 25;
 const MAX_ENTRIES_PER_PAGE: u32 = 1000;
 
@@ -16,31 +21,113 @@ pub struct Page {
     link: Url,
 }
 
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+impl Order {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+}
+
+impl TryFrom<&str> for Order {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "asc" => Ok(Order::Asc),
+            "desc" => Ok(Order::Desc),
+            _ => Err(anyhow!(BadRequest(format!(
+                "unknown sort order: {}",
+                value
+            )))),
+        }
+    }
+}
+
+#[derive(Serialize, Default)]
+pub struct Column {
+    name: String,
+    label: String,
+    /// Link which sorts by this column, descending when it is already sorted ascending.
+    sort_link: Url,
+    sort_order: Option<Order>,
+}
+
+#[derive(Serialize)]
+pub struct Cell {
+    column: String,
+    value: String,
+}
+
+#[derive(Serialize)]
+pub struct Row {
+    link: Option<Url>,
+    /// Value of the selection checkbox.
+    value: Option<String>,
+    cells: Vec<Cell>,
+}
+
 #[derive(Serialize, Default)]
 pub struct TableComponent<T: Serialize + Default> {
+    #[serde(skip)]
     entries: Vec<T>,
+    #[serde(skip)]
+    values: Vec<Map<String, Value>>,
+    #[serde(skip)]
+    links: Vec<Option<Url>>,
+    #[serde(skip)]
+    selection_values: Vec<Option<String>>,
+    #[serde(skip)]
+    formatters: HashMap<String, Formatter>,
+    rows: Vec<Row>,
     pages: Vec<Page>,
     first_page: Option<Url>,
     last_page: Option<Page>,
     previous_page: Option<Url>,
     next_page: Option<Url>,
-    columns: Vec<String>,
+    columns: Vec<Column>,
     max_entries_per_page: u32,
+    /// Identifies the table, hidden columns are remembered under this key in the browser.
+    key: String,
+    /// Id of the element replaced by pages of the table, the table itself when not set.
+    target: Option<String>,
+    /// Name of the selection checkboxes, rows cannot be selected when not set.
+    selection: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct Query {
     page: Option<u32>,
     entries_per_page: Option<u32>,
+    /// One of the columns of the table.
+    sort: Option<String>,
+    /// `asc` (default) or `desc`
+    order: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct QueryNormalized {
     page: u32,
     entries_per_page: u32,
+    sort: Option<(String, Order)>,
 }
 
 impl QueryNormalized {
+    pub fn page(&self) -> u32 {
+        self.page
+    }
+    pub fn entries_per_page(&self) -> u32 {
+        self.entries_per_page
+    }
     pub fn limit(&self) -> i64 {
         self.entries_per_page as i64
     }
@@ -51,22 +138,80 @@ impl QueryNormalized {
             .checked_mul(self.entries_per_page)
             .unwrap_or(0)) as i64
     }
+
+    /// `ORDER BY` expression for the requested sort or `default`. The sort column was
+    /// validated against the columns of the table so it is safe to put into SQL.
+    /// `default` follows the sort column to break ties, it has to be unique or rows
+    /// with equal values could move between pages.
+    pub fn order_by(&self, default: &str) -> String {
+        match &self.sort {
+            Some((column, order)) => {
+                format!("{} {}, {}", column, order.as_str().to_uppercase(), default)
+            }
+            None => default.to_string(),
+        }
+    }
+
+    /// `api_path` can already have a query, e.g. filters of the list.
+    fn link(&self, api_path: &str, page: u32, sort: Option<(&str, Order)>) -> Url {
+        let mut link = format!(
+            "{}{}page={}&entries_per_page={}",
+            api_path,
+            if api_path.contains('?') { "&" } else { "?" },
+            page,
+            self.entries_per_page
+        );
+        if let Some((column, order)) = sort {
+            link.push_str(&format!("&sort={}&order={}", column, order.as_str()));
+        }
+        link
+    }
+
+    fn page_link(&self, api_path: &str, page: u32) -> Url {
+        self.link(
+            api_path,
+            page,
+            self.sort.as_ref().map(|(c, o)| (c.as_str(), *o)),
+        )
+    }
 }
 
 impl Query {
-    pub fn noramlize(&self) -> QueryNormalized {
-        QueryNormalized {
-            page: self.page.unwrap_or(1).min(u32::MAX).max(1),
+    /// Fails when the sort column is not one of the fields of `T`.
+    pub fn noramlize<T: Serialize + Default>(&self) -> anyhow::Result<QueryNormalized> {
+        let sort = match self.sort.as_deref().filter(|s| !s.is_empty()) {
+            None => None,
+            Some(column) => {
+                if !get_struct_fields_names(T::default())?
+                    .iter()
+                    .any(|c| c == column)
+                {
+                    return Err(anyhow!(BadRequest(format!(
+                        "cannot sort by unknown column: {}",
+                        column
+                    ))));
+                }
+                let order = match self.order.as_deref() {
+                    None | Some("") => Order::Asc,
+                    Some(o) => Order::try_from(o)?,
+                };
+                Some((column.to_string(), order))
+            }
+        };
+
+        Ok(QueryNormalized {
+            page: self.page.unwrap_or(1).max(1),
             entries_per_page: self
                 .entries_per_page
                 .unwrap_or(DEFAULT_ENTRIES_PER_PAGE)
-                .max(1)
-                .min(MAX_ENTRIES_PER_PAGE),
-        }
+                .clamp(1, MAX_ENTRIES_PER_PAGE),
+            sort,
+        })
     }
 }
 
 impl<T: Serialize + Default> TableComponent<T> {
+    /// Columns are the fields of `T` in alphabetical order, see `with_columns`.
     pub fn new(
         entries: Vec<T>,
         count: i64,
@@ -75,21 +220,56 @@ impl<T: Serialize + Default> TableComponent<T> {
     ) -> anyhow::Result<Self> {
         let number_of_pages = (count as f64 / query.entries_per_page as f64).ceil() as u32;
         let current_page = query.page;
-        let mut component = Self::default();
-        component.entries = entries;
-        component.columns = get_struct_fields_names(T::default())?;
-        component.max_entries_per_page = query.entries_per_page;
+        let api_path = api_path.as_ref();
+        let columns = get_struct_fields_names(T::default())?
+            .into_iter()
+            .map(|name| {
+                let sort_order = query
+                    .sort
+                    .as_ref()
+                    .filter(|(c, _)| *c == name)
+                    .map(|(_, o)| *o);
+                let next_order = match sort_order {
+                    Some(Order::Asc) => Order::Desc,
+                    _ => Order::Asc,
+                };
+                Column {
+                    sort_link: query.link(api_path, 1, Some((&name, next_order))),
+                    sort_order,
+                    label: name.clone(),
+                    name,
+                }
+            })
+            .collect();
+        let values = entries
+            .iter()
+            .map(|e| match serde_json::to_value(e)? {
+                Value::Object(m) => Ok(m),
+                _ => Err(anyhow!("it should be an object")),
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let mut component = Self {
+            links: entries.iter().map(|_| None).collect(),
+            selection_values: entries.iter().map(|_| None).collect(),
+            entries,
+            values,
+            columns,
+            max_entries_per_page: query.entries_per_page,
+            key: api_path.split('?').next().unwrap_or_default().to_string(),
+            ..Default::default()
+        };
+        component.update_rows();
         component.pages = (current_page as i32 - 3..=current_page as i32 + 3)
             .filter(|p| *p >= 1)
             .filter(|p| *p <= number_of_pages as i32)
             .map(|p| Page {
                 page_number: p as u32,
                 is_current_page: p == current_page as i32,
-                link: format!("{}?page={}", api_path.as_ref(), p),
+                link: query.page_link(api_path, p as u32),
             })
             .collect();
 
-        if component.pages.len() == 0 {
+        if component.pages.is_empty() {
             return Ok(component);
         }
 
@@ -97,25 +277,122 @@ impl<T: Serialize + Default> TableComponent<T> {
             component.last_page = Some(Page {
                 page_number: number_of_pages,
                 is_current_page: false,
-                link: format!("{}?page={}", api_path.as_ref(), number_of_pages),
+                link: query.page_link(api_path, number_of_pages),
             })
         };
 
         if component.pages.first().unwrap().page_number != 1 {
-            component.first_page = Some(format!("{}?page=1", api_path.as_ref()))
+            component.first_page = Some(query.page_link(api_path, 1))
         };
 
         if current_page != number_of_pages {
-            component.next_page = Some(format!("{}?page={}", api_path.as_ref(), current_page + 1))
+            component.next_page = Some(query.page_link(api_path, current_page + 1))
         };
 
         if current_page != 1 {
-            component.previous_page =
-                Some(format!("{}?page={}", api_path.as_ref(), current_page - 1))
+            component.previous_page = Some(query.page_link(api_path, current_page - 1))
         };
 
         Ok(component)
     }
+
+    /// Shows only `columns`, in the given order.
+    pub fn with_columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns
+            .iter()
+            .filter_map(|name| {
+                let i = self.columns.iter().position(|c| c.name == *name)?;
+                Some(self.columns.remove(i))
+            })
+            .collect();
+        self.update_rows();
+        self
+    }
+
+    pub fn with_label(mut self, column: &str, label: impl Into<String>) -> Self {
+        if let Some(c) = self.columns.iter_mut().find(|c| c.name == column) {
+            c.label = label.into();
+        }
+        self
+    }
+
+    pub fn with_formatter(mut self, column: &str, formatter: Formatter) -> Self {
+        self.formatters.insert(column.to_string(), formatter);
+        self.update_rows();
+        self
+    }
+
+    /// Rows become links to the page returned by `link`.
+    pub fn with_row_link(mut self, link: impl Fn(&T) -> Url) -> Self {
+        self.links = self.entries.iter().map(|e| Some(link(e))).collect();
+        self.update_rows();
+        self
+    }
+
+    /// Adds a checkbox named `name` to every row, its value is returned by `value`.
+    pub fn with_selection(mut self, name: impl Into<String>, value: impl Fn(&T) -> String) -> Self {
+        self.selection = Some(name.into());
+        self.selection_values = self.entries.iter().map(|e| Some(value(e))).collect();
+        self.update_rows();
+        self
+    }
+
+    pub fn with_target(mut self, id: impl Into<String>) -> Self {
+        self.target = Some(id.into());
+        self
+    }
+
+    fn update_rows(&mut self) {
+        self.rows = self
+            .values
+            .iter()
+            .zip(self.links.iter())
+            .zip(self.selection_values.iter())
+            .map(|((v, link), value)| Row {
+                link: link.clone(),
+                value: value.clone(),
+                cells: self
+                    .columns
+                    .iter()
+                    .map(|c| {
+                        let value = v.get(&c.name).unwrap_or(&Value::Null);
+                        Cell {
+                            column: c.name.clone(),
+                            value: match self.formatters.get(&c.name) {
+                                Some(f) => f(value, v),
+                                None => text(value),
+                            },
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+    }
+}
+
+fn text(v: &Value) -> String {
+    match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    }
+}
+
+/// Amount with the symbol of the `currency` column of the row, like the `money` template helper.
+pub fn money(v: &Value, row: &Map<String, Value>) -> String {
+    let a = match v {
+        Value::String(s) => s.parse::<f64>().ok(),
+        v => v.as_f64(),
+    };
+    let currency = row.get("currency").map(text).unwrap_or_default();
+    a.map(|a| currency::format_amount(a, &currency))
+        .unwrap_or_default()
+}
+
+/// Date part of a date or a timestamp.
+pub fn date(v: &Value, _row: &Map<String, Value>) -> String {
+    let s = text(v);
+    s.get(..10).unwrap_or(&s).to_string()
 }
 
 fn get_struct_fields_names(s: impl Serialize) -> anyhow::Result<Vec<String>> {
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use crate::{
+    currency,
+    error::BadRequest,
+    front::{accounts::AppMessage, components::table, AppState},
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Multipart, Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Form, Router,
+};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/base", axum::routing::post(post_base))
+        .route("/rates", axum::routing::get(get_rates))
+        .route("/rates", axum::routing::post(post_rate))
+        .route("/rates/import", axum::routing::post(import_rates))
+        .route("/unconverted", axum::routing::get(get_unconverted))
+}
+
+#[derive(Deserialize)]
+struct BaseForm {
+    base_currency: String,
+}
+
+#[axum::debug_handler]
+async fn post_base(
+    State(s): State<AppState>,
+    Form(f): Form<BaseForm>,
+) -> Result<Response, AppMessage> {
+    let code = f.base_currency.trim().to_uppercase();
+    currency::set_base_currency(&s.p, &code)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok(rates_updated(AppMessage::new_info_notification(
+        format!("reports are shown in {}", code),
+        &s,
+    )))
+}
+
+#[axum::debug_handler]
+async fn get_rates(
+    State(s): State<AppState>,
+    Query(q): Query<table::Query>,
+) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Default, Serialize)]
+    struct Record {
+        rate_date: NaiveDate,
+        currency: String,
+        quote: String,
+        rate: BigDecimal,
+        source: String,
+    }
+
+    let q = q
+        .noramlize::<Record>()
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM exchange_rate")
+        .fetch_one(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let records = sqlx::query_as::<_, Record>(&format!(
+        r#"
+        SELECT rate_date, currency, quote, rate, source
+        FROM exchange_rate
+        ORDER BY {}
+        LIMIT $1
+        OFFSET $2
+        "#,
+        q.order_by("rate_date DESC, currency ASC, quote ASC")
+    ))
+    .bind(q.limit())
+    .bind(q.offset())
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let table = table::TableComponent::<Record>::new(records, count, "/api/currencies/rates", q)
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?
+        .with_columns(&["rate_date", "currency", "quote", "rate", "source"])
+        .with_label("rate_date", "Date")
+        .with_label("currency", "Currency")
+        .with_label("quote", "Quoted in")
+        .with_label("rate", "Rate")
+        .with_label("source", "Source")
+        .with_target("rates-table");
+
+    #[derive(Serialize)]
+    struct Ctx {
+        data: table::TableComponent<Record>,
+    }
+
+    let ret =
+        s.t.render("component.table.hbs", &Ctx { data: table })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct RateForm {
+    currency: String,
+    quote: String,
+    rate_date: NaiveDate,
+    rate: String,
+}
+
+#[axum::debug_handler]
+async fn post_rate(
+    State(s): State<AppState>,
+    Form(f): Form<RateForm>,
+) -> Result<Response, AppMessage> {
+    let rate = currency::Rate {
+        currency: f.currency.trim().to_uppercase(),
+        quote: f.quote.trim().to_uppercase(),
+        date: f.rate_date,
+        rate: BigDecimal::from_str(&f.rate.trim().replace(',', ".")).map_err(|err| {
+            AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("invalid rate: {}", err))),
+                &s,
+            )
+        })?,
+    };
+    if !currency::is_valid_code(&rate.currency) || !currency::is_valid_code(&rate.quote) {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(
+                "currency should be a three letter code, e.g. EUR".into()
+            )),
+            &s,
+        ));
+    }
+    if rate.currency == rate.quote {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("currency cannot be quoted in itself".into())),
+            &s,
+        ));
+    }
+    if rate.rate <= BigDecimal::zero() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("rate has to be greater than zero".into())),
+            &s,
+        ));
+    }
+
+    currency::save_rates(&s.p, &[rate], currency::SOURCE_MANUAL)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok(rates_updated(AppMessage::new_info_notification(
+        "rate saved",
+        &s,
+    )))
+}
+
+/// Form field `file` with an NBP or ECB CSV file.
+#[axum::debug_handler]
+async fn import_rates(
+    State(s): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<Response, AppMessage> {
+    let mut count = 0;
+    while let Some(field) = multipart.next_field().await.map_err(|err| {
+        AppMessage::new_error_notification(anyhow!(BadRequest(err.body_text())), &s)
+    })? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let bytes = field.bytes().await.map_err(|err| {
+            AppMessage::new_error_notification(anyhow!(BadRequest(err.body_text())), &s)
+        })?;
+        // NBP files are encoded in windows-1250, only the currency names are not ASCII
+        let content = String::from_utf8_lossy(&bytes);
+        let (rates, source) = currency::parse_rates(&content)
+            .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+        count += currency::save_rates(&s.p, &rates, source)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    }
+
+    Ok(rates_updated(AppMessage::new_info_notification(
+        format!("{} rates imported", count),
+        &s,
+    )))
+}
+
+/// Warning about entries left out of reports for a missing exchange rate, shown
+/// on every page. Empty when all entries can be converted.
+#[axum::debug_handler]
+async fn get_unconverted(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(Serialize)]
+    struct Ctx {
+        unconverted: Vec<currency::Unconverted>,
+    }
+
+    let unconverted = currency::unconverted(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let ret =
+        s.t.render("api.currencies.unconverted.hbs", &Ctx { unconverted })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+/// Tells the page to reload the table of rates.
+fn rates_updated(msg: AppMessage) -> Response {
+    let mut ret = msg.into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("rates-updated"));
+    ret
+}
@@ -0,0 +1,44 @@
+pub mod api;
+
+use super::{accounts::AppMessage, AppState};
+use crate::currency;
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+use serde::Serialize;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(get))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    // currencies of entries which reports have to convert
+    let used: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT currency FROM entry ORDER BY currency ASC")
+            .fetch_all(&s.p)
+            .await
+            .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        base_currency: String,
+        used: Vec<String>,
+        today: String,
+    }
+
+    let res =
+        s.t.render(
+            "currencies.get.hbs",
+            &Ctx {
+                base_currency,
+                used,
+                today: chrono::Local::now().date_naive().to_string(),
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
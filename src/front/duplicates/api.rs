@@ -0,0 +1,107 @@
+use crate::{
+    duplicates::{self, Candidate, DEFAULT_MAX_DAYS, MAX_DAYS},
+    error::BadRequest,
+    front::{accounts::AppMessage, AppState},
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Form, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/merge", axum::routing::post(merge))
+        .route("/dismiss", axum::routing::post(dismiss))
+}
+
+#[derive(Deserialize)]
+struct DuplicatesQuery {
+    days: Option<i32>,
+}
+
+#[axum::debug_handler]
+async fn get(
+    State(s): State<AppState>,
+    Query(q): Query<DuplicatesQuery>,
+) -> Result<Response, AppMessage> {
+    let days = q.days.unwrap_or(DEFAULT_MAX_DAYS);
+    if !(0..=MAX_DAYS).contains(&days) {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(format!(
+                "days have to be between 0 and {}",
+                MAX_DAYS
+            ))),
+            &s,
+        ));
+    }
+
+    let candidates = duplicates::find(&s.p, days)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        candidates: Vec<Candidate>,
+    }
+
+    let ret =
+        s.t.render("api.duplicates.get.hbs", &Ctx { candidates })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct MergeForm {
+    keep: Uuid,
+    remove: Uuid,
+}
+
+#[axum::debug_handler]
+async fn merge(
+    State(s): State<AppState>,
+    Form(f): Form<MergeForm>,
+) -> Result<Response, AppMessage> {
+    duplicates::merge(&s.p, f.keep, f.remove)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok(duplicates_updated(AppMessage::new_info_notification(
+        "entries merged",
+        &s,
+    )))
+}
+
+#[derive(Deserialize)]
+struct DismissForm {
+    a: Uuid,
+    b: Uuid,
+}
+
+#[axum::debug_handler]
+async fn dismiss(
+    State(s): State<AppState>,
+    Form(f): Form<DismissForm>,
+) -> Result<Response, AppMessage> {
+    duplicates::dismiss(&s.p, f.a, f.b)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok(duplicates_updated(AppMessage::new_info_notification(
+        "marked as not a duplicate",
+        &s,
+    )))
+}
+
+/// Tells the review page to reload the list of candidates.
+fn duplicates_updated(msg: AppMessage) -> Response {
+    let mut ret = msg.into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("duplicates-updated"));
+    ret
+}
@@ -0,0 +1,31 @@
+pub mod api;
+
+use super::{accounts::AppMessage, AppState};
+use crate::duplicates::{DEFAULT_MAX_DAYS, MAX_DAYS};
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+use serde::Serialize;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(get))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(Serialize)]
+    struct Ctx {
+        default_days: i32,
+        days: Vec<i32>,
+    }
+
+    let res =
+        s.t.render(
+            "duplicates.get.hbs",
+            &Ctx {
+                default_days: DEFAULT_MAX_DAYS,
+                days: (0..=7).chain([14, MAX_DAYS]).collect(),
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
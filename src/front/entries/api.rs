@@ -0,0 +1,813 @@
+use std::{path::Path, str::FromStr};
+
+use crate::{
+    attachments, currency,
+    error::{BadRequest, NotFound},
+    front::{accounts::AppMessage, AppState},
+    payees,
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Form, Router,
+};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, Pool, Postgres};
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::post(post))
+        .route("/edit", axum::routing::post(post_edit))
+        .route("/audit", axum::routing::get(get_audit))
+        .route("/bulk", axum::routing::post(post_bulk))
+        .route("/bulk/undo", axum::routing::post(post_bulk_undo))
+        .nest("/attachments", super::attachments::new_router())
+}
+
+#[derive(Deserialize)]
+struct NewEntryForm {
+    account_id: Uuid,
+    accounting_date: String,
+    /// `expense` or `income`, the amount is entered without a sign.
+    kind: String,
+    amount: String,
+    currency: String,
+    sender_or_receiver: String,
+    title: String,
+    category: String,
+    note: String,
+}
+
+/// Adds an entry which is not in any bank file. Its reference number is generated
+/// and it is marked as manual so it can be told apart from imported entries.
+#[axum::debug_handler]
+async fn post(
+    State(s): State<AppState>,
+    Form(f): Form<NewEntryForm>,
+) -> Result<Response, AppMessage> {
+    let accounting_date = NaiveDate::from_str(f.accounting_date.trim()).map_err(|err| {
+        AppMessage::new_error_notification(anyhow!(BadRequest(format!("date: {}", err))), &s)
+    })?;
+    let amount = BigDecimal::from_str(&f.amount.replace(',', ".").replace(' ', ""))
+        .map_err(|err| {
+            AppMessage::new_error_notification(anyhow!(BadRequest(format!("amount: {}", err))), &s)
+        })?
+        .abs();
+    if amount.is_zero() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("amount cannot be zero".into())),
+            &s,
+        ));
+    }
+    let amount = match f.kind.as_str() {
+        "expense" => -amount,
+        "income" => amount,
+        kind => {
+            return Err(AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("unknown kind: {}", kind))),
+                &s,
+            ))
+        }
+    };
+    let currency = f.currency.trim().to_uppercase();
+    if !currency::is_valid_code(&currency) {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(
+                "currency should be a three letter code, e.g. PLN".into()
+            )),
+            &s,
+        ));
+    }
+    if f.title.trim().is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("title cannot be empty".into())),
+            &s,
+        ));
+    }
+
+    let source_account: String = sqlx::query_scalar(
+        "SELECT reference FROM account_reference WHERE account_id = $1 ORDER BY reference LIMIT 1",
+    )
+    .bind(f.account_id)
+    .fetch_optional(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?
+    .ok_or(anyhow!(NotFound("account has no reference".into())))
+    .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let category = match f.category.trim() {
+        "" => "Other",
+        c => c,
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO entry (
+            accounting_date,
+            currency_date,
+            sender_or_receiver,
+            address,
+            source_account,
+            destination_account,
+            title,
+            amount,
+            currency,
+            reference_number,
+            operation_type,
+            category,
+            original_title,
+            original_category,
+            note,
+            manual
+        ) VALUES (
+            $1,
+            $1,
+            $2,
+            '',
+            $3,
+            '',
+            $4,
+            $5,
+            $6,
+            'MANUAL-' || gen_random_uuid(),
+            'Manual',
+            $7,
+            $4,
+            $7,
+            $8,
+            TRUE
+        )
+        "#,
+    )
+    .bind(accounting_date)
+    .bind(f.sender_or_receiver.trim())
+    .bind(source_account)
+    .bind(f.title.trim())
+    .bind(amount)
+    .bind(currency)
+    .bind(category)
+    .bind(f.note.trim())
+    .execute(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    if let Err(err) = payees::link_entries(&s.p).await {
+        log::error!("cannot link entries to payees: {}", err);
+    }
+
+    Ok(AppMessage::new_info_notification("entry added", &s).into_response())
+}
+
+#[derive(Deserialize)]
+struct EditForm {
+    entry_id: Uuid,
+    category: String,
+    title: String,
+    note: String,
+    /// Comma separated.
+    tags: String,
+    /// Checkbox, sent only when checked.
+    excluded_from_reports: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct Editable {
+    category: String,
+    title: String,
+    note: String,
+    tags: Vec<String>,
+    excluded_from_reports: bool,
+}
+
+#[axum::debug_handler]
+async fn post_edit(
+    State(s): State<AppState>,
+    Form(f): Form<EditForm>,
+) -> Result<Response, AppMessage> {
+    let mut tags: Vec<String> = Vec::new();
+    for t in f.tags.split(',').map(str::trim) {
+        if !t.is_empty() && !tags.iter().any(|x| x == t) {
+            tags.push(t.to_string());
+        }
+    }
+    let new = Editable {
+        category: f.category.trim().to_string(),
+        title: f.title.trim().to_string(),
+        note: f.note.trim().to_string(),
+        tags,
+        excluded_from_reports: f.excluded_from_reports.is_some(),
+    };
+    for (field, value) in [("title", &new.title), ("category", &new.category)] {
+        if value.is_empty() {
+            return Err(AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("{} cannot be empty", field))),
+                &s,
+            ));
+        }
+    }
+
+    let mut tx =
+        s.p.begin()
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let old = sqlx::query_as::<_, Editable>(
+        r#"
+        SELECT
+            category,
+            title,
+            note,
+            tags,
+            excluded_from_reports
+        FROM entry
+        WHERE id = $1
+        FOR UPDATE
+        "#,
+    )
+    .bind(f.entry_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?
+    .ok_or(anyhow!(NotFound("entry not found".into())))
+    .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let changes: Vec<(&str, String, String)> = [
+        ("category", old.category, new.category.clone()),
+        ("title", old.title, new.title.clone()),
+        ("note", old.note, new.note.clone()),
+        ("tags", old.tags.join(", "), new.tags.join(", ")),
+        (
+            "excluded_from_reports",
+            old.excluded_from_reports.to_string(),
+            new.excluded_from_reports.to_string(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, old, new)| old != new)
+    .collect();
+
+    if changes.is_empty() {
+        return Ok(AppMessage::new_info_notification("nothing changed", &s).into_response());
+    }
+
+    for (field, old_value, new_value) in &changes {
+        sqlx::query(
+            "INSERT INTO entry_audit (entry_id, field, old_value, new_value) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(f.entry_id)
+        .bind(field)
+        .bind(old_value)
+        .bind(new_value)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    }
+
+    // entries imported before original values were stored get them on the first edit
+    sqlx::query(
+        r#"
+        UPDATE entry
+        SET
+            original_title = COALESCE(original_title, title),
+            original_category = COALESCE(original_category, category),
+            category = $2,
+            title = $3,
+            note = $4,
+            tags = $5,
+            excluded_from_reports = $6
+        WHERE id = $1
+        "#,
+    )
+    .bind(f.entry_id)
+    .bind(&new.category)
+    .bind(&new.title)
+    .bind(&new.note)
+    .bind(&new.tags)
+    .bind(new.excluded_from_reports)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    tx.commit()
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let mut ret = AppMessage::new_info_notification(
+        format!(
+            "changed {}",
+            changes
+                .iter()
+                .map(|(field, _, _)| field.replace('_', " "))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        &s,
+    )
+    .into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("entry-updated"));
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    entry_id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn get_audit(
+    State(s): State<AppState>,
+    Query(q): Query<AuditQuery>,
+) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        field: String,
+        old_value: String,
+        new_value: String,
+        changed_at: String,
+    }
+
+    let changes = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            field,
+            old_value,
+            new_value,
+            to_char(changed_at, 'YYYY-MM-DD HH24:MI') AS changed_at
+        FROM entry_audit
+        WHERE entry_id = $1
+        ORDER BY entry_audit.changed_at DESC
+        "#,
+    )
+    .bind(q.entry_id)
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        changes: Vec<Record>,
+    }
+
+    let ret =
+        s.t.render("api.entries.audit.hbs", &Ctx { changes })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+/// Fields of `entry` changed by bulk actions, restored by undo.
+const BULK_FIELDS: [&str; 5] = [
+    "category",
+    "original_category",
+    "tags",
+    "excluded_from_reports",
+    "is_transfer",
+];
+
+/// Bulk actions older than this cannot be undone, their snapshots are removed
+/// together with the files of deleted attachments.
+const BULK_ACTION_TTL_HOURS: i32 = 24;
+
+/// Rows of other tables which reference entries and are removed with them
+/// (`ON DELETE CASCADE`), as `(table, condition)`. Deleted entries are `ANY($1)`.
+const BULK_DEPENDENTS: [(&str, &str); 4] = [
+    ("entry_audit", "entry_id = ANY($1)"),
+    ("attachment", "entry_id = ANY($1)"),
+    (
+        "duplicate_dismissal",
+        "entry_a = ANY($1) OR entry_b = ANY($1)",
+    ),
+    ("merged_reference", "entry_id = ANY($1)"),
+];
+
+/// Audit and change queries of bulk actions. `$1` is the array of selected
+/// entries and `$2` the value entered by the user, if the action takes one.
+/// Audit rows are written before the change, so they see old values.
+fn bulk_queries(action: &str) -> Option<(Option<&'static str>, &'static str)> {
+    match action {
+        "category" => Some((
+            Some(
+                r#"
+                INSERT INTO entry_audit (entry_id, field, old_value, new_value)
+                SELECT id, 'category', category, $2
+                FROM entry
+                WHERE id = ANY($1) AND category <> $2
+                "#,
+            ),
+            r#"
+            UPDATE entry
+            SET
+                original_category = COALESCE(original_category, category),
+                category = $2
+            WHERE id = ANY($1) AND category <> $2
+            "#,
+        )),
+        "tag" => Some((
+            Some(
+                r#"
+                INSERT INTO entry_audit (entry_id, field, old_value, new_value)
+                SELECT
+                    id,
+                    'tags',
+                    array_to_string(tags, ', '),
+                    array_to_string(array_append(tags, $2), ', ')
+                FROM entry
+                WHERE id = ANY($1) AND NOT ($2 = ANY(tags))
+                "#,
+            ),
+            r#"
+            UPDATE entry
+            SET tags = array_append(tags, $2)
+            WHERE id = ANY($1) AND NOT ($2 = ANY(tags))
+            "#,
+        )),
+        "exclude" => Some((
+            Some(
+                r#"
+                INSERT INTO entry_audit (entry_id, field, old_value, new_value)
+                SELECT id, 'excluded_from_reports', 'false', 'true'
+                FROM entry
+                WHERE id = ANY($1) AND NOT excluded_from_reports
+                "#,
+            ),
+            r#"
+            UPDATE entry
+            SET excluded_from_reports = TRUE
+            WHERE id = ANY($1) AND NOT excluded_from_reports
+            "#,
+        )),
+        "transfer" => Some((
+            Some(
+                r#"
+                INSERT INTO entry_audit (entry_id, field, old_value, new_value)
+                SELECT id, 'is_transfer', 'false', 'true'
+                FROM entry
+                WHERE id = ANY($1) AND NOT is_transfer
+                "#,
+            ),
+            r#"
+            UPDATE entry
+            SET is_transfer = TRUE
+            WHERE id = ANY($1) AND NOT is_transfer
+            "#,
+        )),
+        "delete" => Some((None, "DELETE FROM entry WHERE id = ANY($1)")),
+        _ => None,
+    }
+}
+
+/// Removes bulk actions which can no longer be undone and the files of
+/// attachments deleted by them.
+async fn prune_bulk_actions(p: &Pool<Postgres>, dir: &Path) -> anyhow::Result<()> {
+    let attachments: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        WITH expired AS (
+            DELETE FROM bulk_action
+            WHERE created_at < NOW() - make_interval(hours => $1)
+            RETURNING dependents
+        )
+        SELECT (a->>'id')::uuid
+        FROM expired, jsonb_array_elements(expired.dependents->'attachment') a
+        WHERE NOT EXISTS (SELECT 1 FROM attachment WHERE id = (a->>'id')::uuid)
+        "#,
+    )
+    .bind(BULK_ACTION_TTL_HOURS)
+    .fetch_all(p)
+    .await?;
+
+    for id in attachments {
+        attachments::remove(dir, id).await?;
+    }
+    Ok(())
+}
+
+/// Applies `action` to entries selected in the table, form fields are `action`,
+/// `value` and `entry_id` repeated for every selected entry. Affected entries are
+/// saved first so the action can be undone. Deleted entries are saved with the
+/// rows which reference them, their attachment files are kept until the action
+/// expires.
+#[axum::debug_handler]
+async fn post_bulk(
+    State(s): State<AppState>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Response, AppMessage> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.trim().to_string())
+            .unwrap_or_default()
+    };
+    let (action, value) = (field("action"), field("value"));
+    let ids = fields
+        .iter()
+        .filter(|(k, _)| k == "entry_id")
+        .map(|(_, v)| Uuid::from_str(v))
+        .collect::<Result<Vec<Uuid>, _>>()
+        .map_err(|err| {
+            AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("invalid entry id: {}", err))),
+                &s,
+            )
+        })?;
+
+    if ids.is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("no entries selected".into())),
+            &s,
+        ));
+    }
+    let (audit, change) = bulk_queries(&action)
+        .ok_or(anyhow!(BadRequest(format!("unknown action: {}", action))))
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    let takes_value = matches!(action.as_str(), "category" | "tag");
+    if takes_value && value.is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(format!("{} cannot be empty", action))),
+            &s,
+        ));
+    }
+
+    let mut tx =
+        s.p.begin()
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    if let Err(err) = prune_bulk_actions(&s.p, &s.config.attachments_dir).await {
+        log::error!("cannot remove expired bulk actions: {:#}", err);
+    }
+
+    let dependents = if action == "delete" {
+        BULK_DEPENDENTS
+            .iter()
+            .map(|(table, condition)| {
+                format!(
+                    "'{table}', (SELECT COALESCE(jsonb_agg(to_jsonb(t)), '[]') FROM {table} t WHERE {condition})",
+                    table = table,
+                    condition = condition
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    } else {
+        String::new()
+    };
+    let bulk_action_id: Uuid = sqlx::query_scalar(&format!(
+        r#"
+        INSERT INTO bulk_action (action, snapshot, dependents)
+        SELECT $2, COALESCE(jsonb_agg(to_jsonb(e)), '[]'), jsonb_build_object({})
+        FROM entry e
+        WHERE e.id = ANY($1)
+        RETURNING id
+        "#,
+        dependents
+    ))
+    .bind(&ids)
+    .bind(&action)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    // the change runs last, so its count is the one reported
+    let mut affected = 0;
+    for sql in audit.into_iter().chain([change]) {
+        let mut q = sqlx::query(sql).bind(&ids);
+        if takes_value {
+            q = q.bind(&value);
+        }
+        affected = q
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?
+            .rows_affected();
+    }
+
+    // undo restores only entries which still have these values
+    if action != "delete" {
+        sqlx::query(
+            r#"
+            UPDATE bulk_action
+            SET changed = (
+                SELECT COALESCE(jsonb_agg(to_jsonb(e)), '[]') FROM entry e WHERE e.id = ANY($2)
+            )
+            WHERE id = $1
+            "#,
+        )
+        .bind(bulk_action_id)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        info: String,
+        bulk_action_id: Uuid,
+    }
+
+    let mut ret =
+        s.t.render(
+            "api.entries.bulk.hbs",
+            &Ctx {
+                info: format!(
+                    "{} entries {}",
+                    affected,
+                    if action == "delete" {
+                        "deleted"
+                    } else {
+                        "changed"
+                    }
+                ),
+                bulk_action_id,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("entries-updated"));
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct UndoQuery {
+    id: Uuid,
+}
+
+/// Restores entries saved by a bulk action. Deleted entries are inserted again
+/// with the rows which referenced them, unless an entry with the same reference
+/// number was imported since. Changed entries get their old values back, unless
+/// they were edited after the action.
+#[axum::debug_handler]
+async fn post_bulk_undo(
+    State(s): State<AppState>,
+    Query(q): Query<UndoQuery>,
+) -> Result<Response, AppMessage> {
+    let mut tx =
+        s.p.begin()
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let (action, snapshot, changed, dependents): (
+        String,
+        serde_json::Value,
+        serde_json::Value,
+        serde_json::Value,
+    ) = sqlx::query_as(
+        r#"
+        DELETE FROM bulk_action
+        WHERE id = $1 AND created_at >= NOW() - make_interval(hours => $2)
+        RETURNING action, snapshot, changed, dependents
+        "#,
+    )
+    .bind(q.id)
+    .bind(BULK_ACTION_TTL_HOURS)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?
+    .ok_or(anyhow!(BadRequest(
+        "action was already undone or has expired".into()
+    )))
+    .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let total = snapshot.as_array().map_or(0, |a| a.len() as u64);
+    let restored = if action == "delete" {
+        undo_delete(&mut tx, &snapshot, &dependents).await
+    } else {
+        undo_change(&mut tx, &snapshot, &changed).await
+    }
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    tx.commit()
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let mut info = format!("{} entries restored", restored);
+    if restored < total {
+        info.push_str(&format!(
+            ", {} skipped because they were changed, deleted or imported again after the action",
+            total - restored
+        ));
+    }
+    let mut ret = AppMessage::new_info_notification(info, &s).into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("entries-updated"));
+    Ok(ret)
+}
+
+/// Inserts deleted entries and then the rows which referenced them, in the order
+/// of their foreign keys. Returns the number of restored entries.
+async fn undo_delete(
+    tx: &mut PgConnection,
+    snapshot: &serde_json::Value,
+    dependents: &serde_json::Value,
+) -> sqlx::Result<u64> {
+    let restored = sqlx::query(
+        r#"
+        INSERT INTO entry
+        SELECT * FROM jsonb_populate_recordset(NULL::entry, $1)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(snapshot)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    for (table, _) in BULK_DEPENDENTS {
+        let Some(rows) = dependents.get(table) else {
+            continue;
+        };
+        // rows of entries which were not restored, or were deleted since, are skipped
+        let references = match table {
+            "duplicate_dismissal" => {
+                "r.entry_a IN (SELECT id FROM entry) AND r.entry_b IN (SELECT id FROM entry)"
+            }
+            _ => "r.entry_id IN (SELECT id FROM entry)",
+        };
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO {table}
+            SELECT r.* FROM jsonb_populate_recordset(NULL::{table}, $1) r
+            WHERE {references}
+            ON CONFLICT DO NOTHING
+            "#,
+            table = table,
+            references = references
+        ))
+        .bind(rows)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(restored)
+}
+
+/// Writes back old values of entries which still have the values set by the
+/// action, the changes are audited. Returns the number of restored entries.
+async fn undo_change(
+    tx: &mut PgConnection,
+    snapshot: &serde_json::Value,
+    changed: &serde_json::Value,
+) -> sqlx::Result<u64> {
+    let fields = |alias: &str| {
+        BULK_FIELDS
+            .iter()
+            .map(|f| format!("{}.{}", alias, f))
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    // entries edited after the action keep their current values
+    let unchanged_since = format!("({}) IS NOT DISTINCT FROM ({})", fields("e"), fields("c"));
+
+    sqlx::query(&format!(
+        r#"
+        INSERT INTO entry_audit (entry_id, field, old_value, new_value)
+        SELECT e.id, v.field, v.old_value, v.new_value
+        FROM entry e
+        JOIN jsonb_populate_recordset(NULL::entry, $1) r ON r.id = e.id
+        JOIN jsonb_populate_recordset(NULL::entry, $2) c ON c.id = e.id
+        CROSS JOIN LATERAL (VALUES
+            ('category', e.category, r.category),
+            ('tags', array_to_string(e.tags, ', '), array_to_string(r.tags, ', ')),
+            ('excluded_from_reports', e.excluded_from_reports::text, r.excluded_from_reports::text),
+            ('is_transfer', e.is_transfer::text, r.is_transfer::text)
+        ) AS v(field, old_value, new_value)
+        WHERE {} AND v.old_value <> v.new_value
+        "#,
+        unchanged_since
+    ))
+    .bind(snapshot)
+    .bind(changed)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(sqlx::query(&format!(
+        r#"
+        UPDATE entry e
+        SET {}
+        FROM jsonb_populate_recordset(NULL::entry, $1) r
+        JOIN jsonb_populate_recordset(NULL::entry, $2) c ON c.id = r.id
+        WHERE e.id = r.id AND {}
+        "#,
+        BULK_FIELDS
+            .iter()
+            .map(|f| format!("{f} = r.{f}", f = f))
+            .collect::<Vec<String>>()
+            .join(", "),
+        unchanged_since
+    ))
+    .bind(snapshot)
+    .bind(changed)
+    .execute(&mut *tx)
+    .await?
+    .rows_affected())
+}
@@ -0,0 +1,253 @@
+use crate::{
+    attachments,
+    error::{BadRequest, NotFound},
+    front::{accounts::AppMessage, AppState},
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Multipart, Query, State},
+    http::{header, HeaderValue},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/", axum::routing::post(post))
+        .route("/", axum::routing::delete(delete))
+        .route("/file", axum::routing::get(get_file))
+}
+
+#[derive(Deserialize)]
+struct EntryQuery {
+    entry_id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn get(
+    State(s): State<AppState>,
+    Query(q): Query<EntryQuery>,
+) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        id: Uuid,
+        file_name: String,
+        content_type: String,
+        size_kb: i64,
+        created_at: String,
+    }
+
+    let attachments = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            id,
+            file_name,
+            content_type,
+            CEIL(size / 1024.0)::BIGINT AS size_kb,
+            to_char(created_at, 'YYYY-MM-DD HH24:MI') AS created_at
+        FROM attachment
+        WHERE entry_id = $1
+        ORDER BY attachment.created_at ASC
+        "#,
+    )
+    .bind(q.entry_id)
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        attachments: Vec<Record>,
+    }
+
+    let ret =
+        s.t.render("api.entries.attachments.hbs", &Ctx { attachments })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+/// Form fields are `entry_id` and one or more `file`.
+#[axum::debug_handler]
+async fn post(State(s): State<AppState>, mut multipart: Multipart) -> Result<Response, AppMessage> {
+    let mut entry_id = None;
+    let mut files = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(|err| {
+        AppMessage::new_error_notification(anyhow!(BadRequest(err.body_text())), &s)
+    })? {
+        match field.name() {
+            Some("entry_id") => {
+                let text = field.text().await.map_err(|err| {
+                    AppMessage::new_error_notification(anyhow!(BadRequest(err.body_text())), &s)
+                })?;
+                entry_id = Some(text.parse::<Uuid>().map_err(|err| {
+                    AppMessage::new_error_notification(
+                        anyhow!(BadRequest(format!("invalid entry id: {}", err))),
+                        &s,
+                    )
+                })?);
+            }
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or("attachment").to_string();
+                let bytes = field.bytes().await.map_err(|err| {
+                    AppMessage::new_error_notification(anyhow!(BadRequest(err.body_text())), &s)
+                })?;
+                if bytes.is_empty() {
+                    continue;
+                }
+                if bytes.len() > s.config.upload_limit() {
+                    return Err(AppMessage::new_error_notification(
+                        anyhow!(BadRequest(format!(
+                            "{} is bigger than {} MB",
+                            file_name, s.config.upload_limit_mb
+                        ))),
+                        &s,
+                    ));
+                }
+                let content_type = attachments::content_type(&bytes)
+                    .ok_or(anyhow!(BadRequest(format!(
+                        "{}: only PDF, JPEG and PNG files are allowed",
+                        file_name
+                    ))))
+                    .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+                files.push((file_name, content_type, bytes));
+            }
+            _ => {}
+        }
+    }
+
+    let entry_id = entry_id
+        .ok_or(anyhow!(BadRequest("entry is not set".into())))
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    if files.is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("no file selected".into())),
+            &s,
+        ));
+    }
+
+    // all files or none of them, written files are removed when a later one fails
+    let mut saved = Vec::new();
+    if let Err(err) = save_all(&s, entry_id, &files, &mut saved).await {
+        for id in saved {
+            if let Err(err) = attachments::remove(&s.config.attachments_dir, id).await {
+                log::error!("cannot remove attachment file {}: {:#}", id, err);
+            }
+        }
+        return Err(AppMessage::new_error_notification(err, &s));
+    }
+
+    Ok(attachments_updated(AppMessage::new_info_notification(
+        format!("{} files attached", files.len()),
+        &s,
+    )))
+}
+
+/// Inserts the rows in one transaction, committed when every file was written.
+/// Ids of written files are added to `saved`.
+async fn save_all(
+    s: &AppState,
+    entry_id: Uuid,
+    files: &[(String, &str, axum::body::Bytes)],
+    saved: &mut Vec<Uuid>,
+) -> anyhow::Result<()> {
+    let mut tx = s.p.begin().await?;
+    for (file_name, content_type, bytes) in files {
+        let id: Uuid = sqlx::query_scalar(
+            r#"
+            INSERT INTO attachment (entry_id, file_name, content_type, size)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id
+            "#,
+        )
+        .bind(entry_id)
+        .bind(file_name)
+        .bind(content_type)
+        .bind(bytes.len() as i64)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        attachments::save(&s.config.attachments_dir, id, bytes).await?;
+        saved.push(id);
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct IdQuery {
+    id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn delete(
+    State(s): State<AppState>,
+    Query(q): Query<IdQuery>,
+) -> Result<Response, AppMessage> {
+    sqlx::query("DELETE FROM attachment WHERE id = $1")
+        .bind(q.id)
+        .execute(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    attachments::remove(&s.config.attachments_dir, q.id)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok(attachments_updated(AppMessage::new_info_notification(
+        "attachment removed",
+        &s,
+    )))
+}
+
+#[axum::debug_handler]
+async fn get_file(
+    State(s): State<AppState>,
+    Query(q): Query<IdQuery>,
+) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow)]
+    struct Record {
+        file_name: String,
+        content_type: String,
+    }
+
+    let r =
+        sqlx::query_as::<_, Record>("SELECT file_name, content_type FROM attachment WHERE id = $1")
+            .bind(q.id)
+            .fetch_optional(&s.p)
+            .await
+            .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?
+            .ok_or(anyhow!(NotFound("attachment not found".into())))
+            .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    let bytes = attachments::read(&s.config.attachments_dir, q.id)
+        .await
+        .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, r.content_type),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename=\"{}\"",
+                    r.file_name.replace(['"', '\\'], "_")
+                ),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Tells the details page to reload the list of attachments.
+fn attachments_updated(msg: AppMessage) -> Response {
+    let mut ret = msg.into_response();
+    ret.headers_mut().insert(
+        "HX-Trigger",
+        HeaderValue::from_static("attachments-updated"),
+    );
+    ret
+}
@@ -0,0 +1,66 @@
+pub mod api;
+pub mod attachments;
+
+use super::{accounts::AppMessage, AppState};
+use crate::currency;
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/new", axum::routing::get(get_new))
+}
+
+/// Form for entries which are not in any bank file, e.g. cash spending.
+#[axum::debug_handler]
+async fn get_new(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Account {
+        id: Uuid,
+        name: String,
+    }
+
+    let accounts = sqlx::query_as::<_, Account>(
+        r#"
+        SELECT id, name
+        FROM account a
+        WHERE EXISTS (SELECT 1 FROM account_reference r WHERE r.account_id = a.id)
+        ORDER BY name ASC
+        "#,
+    )
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    let categories: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT category FROM entry ORDER BY category ASC")
+            .fetch_all(&s.p)
+            .await
+            .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        accounts: Vec<Account>,
+        categories: Vec<String>,
+        today: String,
+        base_currency: String,
+    }
+
+    let res =
+        s.t.render(
+            "entries.new.hbs",
+            &Ctx {
+                accounts,
+                categories,
+                today: chrono::Local::now().date_naive().to_string(),
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
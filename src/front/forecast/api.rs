@@ -0,0 +1,286 @@
+use std::str::FromStr;
+
+use crate::{
+    currency,
+    error::BadRequest,
+    forecast,
+    front::{accounts::AppMessage, components::line_chart::LineChart, AppState},
+    recurring::Period,
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Form, Router,
+};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const DEFAULT_MONTHS: u32 = 3;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/planned", axum::routing::get(get_planned))
+        .route("/planned", axum::routing::post(post_planned))
+        .route("/planned", axum::routing::delete(delete_planned))
+}
+
+#[derive(Deserialize)]
+struct ForecastQuery {
+    months: Option<u32>,
+    threshold: Option<String>,
+}
+
+#[axum::debug_handler]
+async fn get(
+    State(s): State<AppState>,
+    Query(q): Query<ForecastQuery>,
+) -> Result<Response, AppMessage> {
+    let threshold = match q.threshold.as_deref().map(str::trim) {
+        None | Some("") => BigDecimal::default(),
+        Some(t) => BigDecimal::from_str(&t.replace(',', ".")).map_err(|err| {
+            AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("threshold: {}", err))),
+                &s,
+            )
+        })?,
+    };
+
+    let forecasts = forecast::forecast(&s.p, q.months.unwrap_or(DEFAULT_MONTHS))
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Warning {
+        account: String,
+        date: NaiveDate,
+        balance: BigDecimal,
+        lowest_date: NaiveDate,
+        lowest_balance: BigDecimal,
+        below_zero: bool,
+    }
+
+    let warnings: Vec<Warning> = forecasts
+        .iter()
+        .filter_map(|f| {
+            let (date, balance) = f.first_below(&threshold)?;
+            let (lowest_date, lowest_balance) = f.lowest()?;
+            Some(Warning {
+                account: f.name.clone(),
+                date: *date,
+                balance: balance.clone(),
+                lowest_date: *lowest_date,
+                lowest_balance: lowest_balance.clone(),
+                below_zero: *lowest_balance < BigDecimal::default(),
+            })
+        })
+        .collect();
+
+    #[derive(Serialize)]
+    struct Balance {
+        account: String,
+        opening_balance: BigDecimal,
+        closing_balance: BigDecimal,
+    }
+
+    let balances = forecasts
+        .iter()
+        .map(|f| Balance {
+            account: f.name.clone(),
+            opening_balance: f.opening_balance.clone(),
+            closing_balance: f
+                .balances
+                .last()
+                .map(|(_, b)| b.clone())
+                .unwrap_or_default(),
+        })
+        .collect();
+
+    let dates = forecasts
+        .first()
+        .map(|f| f.balances.iter().map(|(d, _)| d.to_string()).collect())
+        .unwrap_or_default();
+
+    let mut guides = vec![("0".to_string(), 0.0)];
+    if threshold != BigDecimal::default() {
+        guides.push((threshold.to_string(), threshold.to_f64().unwrap_or(0.0)));
+    }
+
+    let chart = LineChart::new(
+        forecasts
+            .iter()
+            .map(|f| {
+                (
+                    f.name.clone(),
+                    f.balances
+                        .iter()
+                        .map(|(_, b)| b.to_f64().unwrap_or(0.0))
+                        .collect(),
+                )
+            })
+            .collect(),
+        dates,
+        guides,
+    );
+
+    #[derive(Serialize)]
+    struct Ctx {
+        chart: LineChart,
+        warnings: Vec<Warning>,
+        balances: Vec<Balance>,
+        threshold: BigDecimal,
+        base_currency: String,
+    }
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let ret =
+        s.t.render(
+            "api.forecast.get.hbs",
+            &Ctx {
+                chart,
+                warnings,
+                balances,
+                threshold,
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[axum::debug_handler]
+async fn get_planned(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        id: Uuid,
+        account: String,
+        name: String,
+        amount: BigDecimal,
+        period: String,
+        next_date: NaiveDate,
+    }
+
+    let planned = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            p.id,
+            a.name AS account,
+            p.name,
+            p.amount,
+            p.period,
+            p.next_date
+        FROM planned_transaction p
+        JOIN account a ON a.id = p.account_id
+        ORDER BY p.next_date ASC
+        "#,
+    )
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        planned: Vec<Record>,
+        base_currency: String,
+    }
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let ret =
+        s.t.render(
+            "api.forecast.planned.hbs",
+            &Ctx {
+                planned,
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct PlannedForm {
+    account_id: Uuid,
+    name: String,
+    amount: String,
+    period: String,
+    next_date: NaiveDate,
+}
+
+#[axum::debug_handler]
+async fn post_planned(
+    State(s): State<AppState>,
+    Form(f): Form<PlannedForm>,
+) -> Result<Response, AppMessage> {
+    let amount =
+        BigDecimal::from_str(&f.amount.replace(',', ".").replace(' ', "")).map_err(|err| {
+            AppMessage::new_error_notification(anyhow!(BadRequest(format!("amount: {}", err))), &s)
+        })?;
+    let period = Period::try_from(f.period.as_str())
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    if f.name.trim().is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("name cannot be empty".into())),
+            &s,
+        ));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO planned_transaction (account_id, name, amount, period, next_date)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(f.account_id)
+    .bind(f.name.trim())
+    .bind(amount)
+    .bind(period.as_str())
+    .bind(f.next_date)
+    .execute(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    Ok(forecast_updated(AppMessage::new_info_notification(
+        "planned transaction added",
+        &s,
+    )))
+}
+
+#[derive(Deserialize)]
+struct DeletePlannedQuery {
+    id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn delete_planned(
+    State(s): State<AppState>,
+    Query(q): Query<DeletePlannedQuery>,
+) -> Result<Response, AppMessage> {
+    sqlx::query("DELETE FROM planned_transaction WHERE id = $1")
+        .bind(q.id)
+        .execute(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    Ok(forecast_updated(AppMessage::new_info_notification(
+        "planned transaction removed",
+        &s,
+    )))
+}
+
+/// Tells the page to reload forecast and list of planned transactions.
+fn forecast_updated(msg: AppMessage) -> Response {
+    let mut ret = msg.into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("forecast-updated"));
+    ret
+}
@@ -0,0 +1,45 @@
+pub mod api;
+
+use super::{accounts::AppMessage, AppState};
+use crate::forecast::{MAX_MONTHS, MIN_MONTHS};
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+use serde::Serialize;
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(get))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Account {
+        id: Uuid,
+        name: String,
+    }
+
+    let accounts = sqlx::query_as::<_, Account>("SELECT id, name FROM account ORDER BY name ASC")
+        .fetch_all(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        accounts: Vec<Account>,
+        min_months: u32,
+        max_months: u32,
+    }
+
+    let res =
+        s.t.render(
+            "forecast.get.hbs",
+            &Ctx {
+                accounts,
+                min_months: MIN_MONTHS,
+                max_months: MAX_MONTHS,
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
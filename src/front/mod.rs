@@ -1,42 +1,58 @@
 pub mod accounts;
 pub mod components;
+pub mod currencies;
+pub mod duplicates;
+pub mod entries;
+pub mod forecast;
+pub mod payees;
+pub mod reports;
+pub mod subscriptions;
 pub mod template;
+pub mod v1;
 
-use std::{
-    collections::HashMap,
-    error::Error,
-    io::{self, Read},
-};
+use std::{collections::HashMap, sync::Arc};
 
 use anyhow::Context;
 use axum::{
-    extract::{connect_info::MockConnectInfo, Multipart, Query, State},
-    http::{status, Request, StatusCode},
+    extract::{
+        multipart::MultipartError, DefaultBodyLimit, Multipart, Path, Query, Request, State,
+    },
+    http::{header, HeaderValue, StatusCode},
     middleware::{self, Next},
-    response::{Html, IntoResponse, Redirect, Response, Result},
+    response::{IntoResponse, Response, Result},
     routing::{get, post},
-    Router, ServiceExt,
+    Router,
 };
 use bigdecimal::{BigDecimal, FromPrimitive};
-use chrono::{format, Datelike, DurationRound, TimeDelta, TimeZone};
-use log::{warn, Record};
+use chrono::{Datelike, Months};
 use serde::{Deserialize, Serialize};
 use sqlx::{prelude::*, types, Pool, Postgres};
-use tower_http::services::ServeDir;
-
-use crate::models;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    assets::{self, Kind},
+    config::Config,
+    currency,
+    error::BadRequest,
+    import, models,
+};
+use components::table;
 
-const MAIN_ACCOUNT_ID: &'static str = "1e7a4379-4fd5-45df-ba1b-fd6f3fc34717";
+const MAIN_ACCOUNT_ID: &str = "1e7a4379-4fd5-45df-ba1b-fd6f3fc34717";
 
 #[derive(Clone)]
 pub struct AppState {
     p: Pool<Postgres>,
     t: template::Template,
+    config: Arc<Config>,
 }
 
-pub async fn start_web_server(p: &Pool<Postgres>) {
+/// In `dev` mode templates are reloaded when they change.
+pub async fn start_web_server(p: &Pool<Postgres>, config: Config, dev: bool) -> anyhow::Result<()> {
     log::info!("loading templates...");
-    let t = template::Template::new();
+    let t = template::Template::new(&config.locale);
+    let config = Arc::new(config);
+    let _watcher = if dev { Some(t.watch()?) } else { None };
 
     // build our application with a single route
     let app = Router::new()
@@ -44,44 +60,108 @@ pub async fn start_web_server(p: &Pool<Postgres>) {
         .route("/details", get(details))
         .route("/expenses", get(expenses))
         .route("/api/entry", get(api_entry))
+        .route("/api/entry/filters", get(api_entry_filters))
         .route("/api/expenses", get(api_expenses))
         .route("/api/upload", post(api_upload))
         .nest("/accounts", accounts::new_router())
         .nest("/api/accounts", accounts::api::new_router())
-        .nest_service("/public", ServeDir::new("./src/front/public"))
-        .with_state(AppState { p: p.clone(), t });
+        .nest("/currencies", currencies::new_router())
+        .nest("/api/currencies", currencies::api::new_router())
+        .nest("/duplicates", duplicates::new_router())
+        .nest("/api/duplicates", duplicates::api::new_router())
+        .nest("/entries", entries::new_router())
+        .nest("/api/entries", entries::api::new_router())
+        .nest("/forecast", forecast::new_router())
+        .nest("/api/forecast", forecast::api::new_router())
+        .nest("/payees", payees::new_router())
+        .nest("/api/payees", payees::api::new_router())
+        .nest("/reports", reports::new_router())
+        .nest("/api/reports", reports::api::new_router())
+        .nest("/subscriptions", subscriptions::new_router())
+        .nest("/api/subscriptions", subscriptions::api::new_router())
+        .nest("/api/v1", v1::new_router())
+        .merge(v1::docs_router())
+        .route("/public/*path", get(public))
+        .layer(DefaultBodyLimit::max(config.upload_limit()))
+        .layer(middleware::from_fn(log_errors))
+        .layer(middleware::from_fn(template::errors_fragment))
+        .with_state(AppState {
+            p: p.clone(),
+            t,
+            config: config.clone(),
+        });
+
+    let listener = tokio::net::TcpListener::bind(&config.bind)
+        .await
+        .with_context(|| format!("cannot listen on {}", config.bind))?;
+    log::info!("open website at http://{}", config.bind);
+    axum::serve(listener, app)
+        .await
+        .context("web server failed")?;
+    Ok(())
+}
+
+/// Logs errors of handlers, see `accounts::AppMessage`, with the request which caused them.
+async fn log_errors(req: Request, next: Next) -> Response {
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let res = next.run(req).await;
+    if let Some(accounts::ErrorMessage(msg)) = res.extensions().get() {
+        if res.status().is_server_error() {
+            log::error!("{} {} -> {}: {}", method, uri, res.status(), msg);
+        } else {
+            log::warn!("{} {} -> {}: {}", method, uri, res.status(), msg);
+        }
+    }
+    res
+}
 
-    // run our app with hyper, listening globally on port 3000
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
-    log::info!("open website at http://localhost:3000");
-    axum::serve(listener, app).await.unwrap();
+/// Static files built into the binary, or from the override directory.
+async fn public(Path(path): Path<String>) -> Response {
+    match assets::get(Kind::Public, &path) {
+        Ok(Some(file)) => (
+            [(header::CONTENT_TYPE, assets::content_type(&file.name))],
+            file.contents,
+        )
+            .into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            log::error!("{:#}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
 }
 
-async fn index(State(s): State<AppState>) -> Response {
+async fn index(State(s): State<AppState>) -> Result<Response, accounts::AppMessage> {
     #[derive(sqlx::FromRow)]
     struct Result {
         goal: Option<types::BigDecimal>,
     }
 
     let res = sqlx::query_as::<_, Result>(
-        "SELECT SUM(amount) as goal FROM entry WHERE EXTRACT(month from NOW()) = EXTRACT(month from accounting_date)",
+        &format!(
+            "SELECT SUM({}) as goal FROM entry e WHERE EXTRACT(month from NOW()) = EXTRACT(month from e.accounting_date)",
+            reports::BASE_AMOUNT
+        ),
     )
     .fetch_one(&s.p)
     .await
-    .unwrap();
+    .map_err(|err| accounts::AppMessage::new_error(anyhow::anyhow!(err), &s))?;
 
     #[derive(Serialize)]
     struct Ctx {
         goal: BigDecimal,
     }
 
-    s.t.render(
-        "index.hbs",
-        &Ctx {
-            goal: res.goal.unwrap_or_default(),
-        },
-    )
-    .unwrap()
+    let ret =
+        s.t.render(
+            "index.hbs",
+            &Ctx {
+                goal: res.goal.unwrap_or_default(),
+            },
+        )
+        .map_err(|err| accounts::AppMessage::new_error(err, &s))?;
+    Ok(ret)
 }
 
 #[derive(Deserialize)]
@@ -89,23 +169,43 @@ struct DetailsQuery {
     entry_id: String,
 }
 
-async fn details(State(s): State<AppState>, Query(q): Query<DetailsQuery>) -> Response {
+async fn details(
+    State(s): State<AppState>,
+    Query(q): Query<DetailsQuery>,
+) -> Result<Response, accounts::AppMessage> {
+    let id = q
+        .entry_id
+        .parse::<uuid::Uuid>()
+        .with_context(|| BadRequest(format!("invalid entry id '{}'", q.entry_id)))
+        .map_err(|err| accounts::AppMessage::new_error(err, &s))?;
+
     let entry: models::Entry =
-        sqlx::query_as::<_, models::Entry>("SELECT * FROM entry WHERE id = $1::uuid LIMIT 1")
-            .bind(q.entry_id)
+        sqlx::query_as::<_, models::Entry>("SELECT * FROM entry WHERE id = $1 LIMIT 1")
+            .bind(id)
             .fetch_one(&s.p)
             .await
-            .unwrap();
+            .context("entry does not exist")
+            .map_err(|err| accounts::AppMessage::new_error(err, &s))?;
+
+    let categories: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT category FROM entry ORDER BY category ASC")
+            .fetch_all(&s.p)
+            .await
+            .map_err(|err| accounts::AppMessage::new_error(anyhow::anyhow!(err), &s))?;
 
     #[derive(Serialize)]
     struct Ctx {
         entry: models::Entry,
+        categories: Vec<String>,
     }
 
-    s.t.render("entry_details.hbs", &Ctx { entry }).unwrap()
+    let ret =
+        s.t.render("entry_details.hbs", &Ctx { entry, categories })
+            .map_err(|err| accounts::AppMessage::new_error(err, &s))?;
+    Ok(ret)
 }
 
-async fn expenses(State(s): State<AppState>) -> Response {
+async fn expenses(State(s): State<AppState>) -> Result<Response, accounts::AppMessage> {
     #[derive(Serialize)]
     struct Ctx {
         years: Vec<u32>,
@@ -113,114 +213,185 @@ async fn expenses(State(s): State<AppState>) -> Response {
         current_month: u32,
     }
 
-    #[derive(sqlx::FromRow, Serialize, Debug)]
-    struct Record {
-        accounting_date: chrono::NaiveDate,
-    }
-    let r1: Record = sqlx::query_as(
-        r#"
-        SELECT accounting_date
-        FROM entry
-        WHERE
-            source_account IN (
-                SELECT reference FROM account_reference WHERE account_id::text = $1
-            ) AND
-            amount < 0
-        ORDER BY accounting_date ASC
-        LIMIT 1
-        "#,
-    )
-    .bind(MAIN_ACCOUNT_ID)
-    .fetch_one(&s.p)
-    .await
-    .unwrap();
-
-    let r2: Record = sqlx::query_as(
+    // years of the first and the last expense, none when there are no expenses yet
+    let (first, last): (Option<chrono::NaiveDate>, Option<chrono::NaiveDate>) = sqlx::query_as(
         r#"
-        SELECT accounting_date
+        SELECT MIN(accounting_date), MAX(accounting_date)
         FROM entry
         WHERE
             source_account IN (
                 SELECT reference FROM account_reference WHERE account_id::text = $1
             ) AND
             amount < 0
-        ORDER BY accounting_date DESC
-        LIMIT 1
         "#,
     )
     .bind(MAIN_ACCOUNT_ID)
     .fetch_one(&s.p)
     .await
-    .unwrap();
+    .map_err(|err| accounts::AppMessage::new_error(anyhow::anyhow!(err), &s))?;
 
     let current_year = chrono::Local::now().year() as u32;
     let current_month = chrono::Local::now().month();
-    let (_, year1) = r1.accounting_date.year_ce();
-    let (_, year2) = r2.accounting_date.year_ce();
-
-    s.t.render(
-        "expenses.hbs",
-        &Ctx {
-            years: (year1..=year2).collect(),
-            current_year,
-            current_month,
-        },
-    )
-    .unwrap()
+    let year = |d: Option<chrono::NaiveDate>| d.map_or(current_year, |d| d.year_ce().1);
+
+    let ret =
+        s.t.render(
+            "expenses.hbs",
+            &Ctx {
+                years: (year(first)..=year(last)).collect(),
+                current_year,
+                current_month,
+            },
+        )
+        .map_err(|err| accounts::AppMessage::new_error(err, &s))?;
+    Ok(ret)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 struct ExpensesQuery {
     max_elements: Option<u32>,
     month: Option<u32>,
     year: Option<u32>,
+    /// `month` (default) or `year`
+    period: Option<String>,
+    /// Number of earlier years to compare the period with.
+    #[param(ignore)]
+    compare: Option<u32>,
+    /// `category` (default) or `merchant`
+    group_by: Option<String>,
 }
 
-async fn api_expenses(State(s): State<AppState>, Query(q): Query<ExpensesQuery>) -> Response {
-    let year = q.year.unwrap_or_else(|| chrono::Local::now().year() as u32);
-    let month = q
-        .month
-        .unwrap_or_else(|| chrono::Local::now().month() as u32);
+const MAX_COMPARE_YEARS: u32 = 5;
+
+impl ExpensesQuery {
+    /// First day of the month or year (inclusive) and of the next one (exclusive),
+    /// the current month by default.
+    fn period(&self) -> (chrono::NaiveDate, chrono::NaiveDate) {
+        let year = self
+            .year
+            .unwrap_or_else(|| chrono::Local::now().year() as u32);
+        let month = self.month.unwrap_or_else(|| chrono::Local::now().month());
+
+        let today = chrono::Local::now().date_naive();
+        let mut r1 = chrono::NaiveDate::from_ymd_opt(year as i32, month, 1)
+            .unwrap_or(today - chrono::Days::new(today.day0() as u64));
+        if self.by_year() {
+            r1 = r1 - Months::new(r1.month0());
+        }
+        let period_months = Months::new(if self.by_year() { 12 } else { 1 });
+        let r2 = r1
+            .checked_add_months(period_months)
+            .unwrap_or(chrono::NaiveDate::MAX);
+        (r1, r2)
+    }
 
-    let r1 = chrono::NaiveDate::from_ymd_opt(year as i32, month, 1)
-        .unwrap_or(chrono::Local::now().date_naive().with_day(1).unwrap());
+    fn by_year(&self) -> bool {
+        self.period.as_deref() == Some("year")
+    }
 
-    let (_, year) = r1.year_ce();
-    let r2 = r1.with_month(r1.month0() + 2).unwrap_or(
-        r1.with_year((year + 1) as i32)
-            .unwrap()
-            .with_month(1)
-            .unwrap(),
-    );
+    fn group_by(&self) -> GroupBy {
+        match self.group_by.as_deref() {
+            Some("merchant") => GroupBy::Merchant,
+            _ => GroupBy::Category,
+        }
+    }
+}
 
-    #[derive(sqlx::FromRow, Serialize, Debug)]
-    struct Record {
-        category: String,
-        amount: BigDecimal,
+/// Column which `sum_by_account` groups entries by.
+#[derive(Clone, Copy)]
+enum GroupBy {
+    Category,
+    SenderOrReceiver,
+    /// Payee name, raw `sender_or_receiver` for entries without a payee.
+    Merchant,
+}
+
+impl GroupBy {
+    fn column(&self) -> &'static str {
+        match self {
+            GroupBy::Category => "e.category",
+            GroupBy::SenderOrReceiver => "e.sender_or_receiver",
+            GroupBy::Merchant => "COALESCE(pe.name, e.sender_or_receiver)",
+        }
     }
-    let mut records: Vec<Record> = sqlx::query_as(
+}
+
+#[derive(sqlx::FromRow, Serialize, Debug, ToSchema)]
+struct GroupedAmount {
+    name: String,
+    #[schema(value_type = String)]
+    amount: BigDecimal,
+}
+
+/// Expenses (`amount < 0`) or income (`amount > 0`) of the account between `from` (inclusive)
+/// and `to` (exclusive) grouped by `group_by`, the biggest amount first.
+/// Transfers between own accounts and entries without an exchange rate are skipped.
+async fn sum_by_account(
+    p: &Pool<Postgres>,
+    account_id: &str,
+    group_by: GroupBy,
+    expenses: bool,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> sqlx::Result<Vec<GroupedAmount>> {
+    sqlx::query_as(&format!(
         r#"
         SELECT 
-            category,
-            SUM(amount) AS amount
-        FROM entry
+            {column} AS name,
+            SUM({amount}) AS amount
+        FROM entry e
+        LEFT JOIN payee pe ON pe.id = e.payee_id
         WHERE 
-            source_account IN (
+            e.source_account IN (
                 SELECT reference FROM account_reference WHERE account_id::text = $1
             ) AND
-            accounting_date >= $2::date AND
-            accounting_date < $3::date AND
-            amount < 0
-        GROUP BY category
-        ORDER BY amount ASC
+            e.accounting_date >= $2::date AND
+            e.accounting_date < $3::date AND
+            e.amount {sign} 0 AND
+            {amount} IS NOT NULL AND
+            {reportable}
+        GROUP BY {column}
+        ORDER BY ABS(SUM({amount})) DESC
         "#,
-    )
-    .bind(MAIN_ACCOUNT_ID)
-    .bind(r1.format("%Y-%m-%d").to_string())
-    .bind(r2.format("%Y-%m-%d").to_string())
-    .fetch_all(&s.p)
+        amount = reports::BASE_AMOUNT,
+        column = group_by.column(),
+        sign = if expenses { "<" } else { ">" },
+        reportable = reports::REPORTABLE_ENTRY,
+    ))
+    .bind(account_id)
+    .bind(from.format("%Y-%m-%d").to_string())
+    .bind(to.format("%Y-%m-%d").to_string())
+    .fetch_all(p)
     .await
-    .unwrap();
+}
+
+/// Expenses of the main account grouped by `group_by`, the biggest expense first.
+async fn expenses_of_main_account(
+    p: &Pool<Postgres>,
+    group_by: GroupBy,
+    from: chrono::NaiveDate,
+    to: chrono::NaiveDate,
+) -> sqlx::Result<Vec<GroupedAmount>> {
+    sum_by_account(p, MAIN_ACCOUNT_ID, group_by, true, from, to).await
+}
+
+async fn api_expenses(
+    State(s): State<AppState>,
+    Query(q): Query<ExpensesQuery>,
+) -> Result<Response, accounts::AppMessage> {
+    let by_year = q.by_year();
+    let group_by = q.group_by();
+    let (r1, r2) = q.period();
+
+    if let Some(years) = q.compare.filter(|y| *y > 0) {
+        return api_expenses_compare(&s, group_by, r1, r2, years.min(MAX_COMPARE_YEARS), by_year)
+            .await;
+    }
+
+    let mut records = expenses_of_main_account(&s.p, group_by, r1, r2)
+        .await
+        .map_err(|err| accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), &s))?;
 
     if let Some(m) = q.max_elements {
         if records.len() > m as usize {
@@ -235,10 +406,15 @@ async fn api_expenses(State(s): State<AppState>, Query(q): Query<ExpensesQuery>)
         height_ratio: String, // from 0 to 100
     }
 
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?;
+
     #[derive(Serialize)]
     struct Ctx {
         expenses: Vec<Element>,
         date_range: String,
+        base_currency: String,
     }
 
     let max = records.first().map(|r| r.amount.clone().abs());
@@ -250,251 +426,574 @@ async fn api_expenses(State(s): State<AppState>, Query(q): Query<ExpensesQuery>)
                 .iter()
                 .map(|r| Element {
                     amount: r.amount.clone(),
-                    category: r.category.clone(),
-                    height_ratio: max.clone().map_or(format!("0"), |m| {
+                    category: r.name.clone(),
+                    height_ratio: max.clone().map_or("0".to_string(), |m| {
                         (r.amount.clone().abs() / m * BigDecimal::from_i32(100).unwrap())
                             .round(0)
                             .to_string()
                     }),
                 })
                 .collect(),
-            date_range: format!(
-                "{} - {}",
-                r1.format("%Y-%m-%d").to_string(),
-                r2.format("%Y-%m-%d").to_string()
-            ),
+            date_range: format!("{} - {}", r1.format("%Y-%m-%d"), r2.format("%Y-%m-%d")),
+            base_currency,
         },
     )
-    .unwrap()
-}
-
-#[derive(Deserialize)]
-struct EntryQuery {
-    page: Option<u32>,
+    .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))
 }
 
-#[axum::debug_handler]
-async fn api_entry(State(s): State<AppState>, Query(query): Query<EntryQuery>) -> Response {
-    let mut current_page = query.page.unwrap_or(1);
-
-    let count: i64 = sqlx::query("SELECT COUNT(*) FROM entry")
-        .fetch_one(&s.p)
-        .await
-        .unwrap()
-        .try_get(0)
-        .unwrap();
-
-    let max_page = (count as f64 / 10.0).ceil() as u32;
-
-    if current_page == 0 {
-        current_page = 1;
-    } else if current_page >= max_page && max_page > 0 {
-        current_page = max_page
+/// Puts expenses of the period next to the same period `years` years earlier.
+async fn api_expenses_compare(
+    s: &AppState,
+    group_by: GroupBy,
+    r1: chrono::NaiveDate,
+    r2: chrono::NaiveDate,
+    years: u32,
+    by_year: bool,
+) -> Result<Response, accounts::AppMessage> {
+    let label_format = if by_year { "%Y" } else { "%Y-%m" };
+
+    let mut periods = Vec::new();
+    for i in 0..=years {
+        let shift = Months::new(12 * i);
+        let (Some(from), Some(to)) = (r1.checked_sub_months(shift), r2.checked_sub_months(shift))
+        else {
+            break;
+        };
+        let amounts: HashMap<String, BigDecimal> =
+            expenses_of_main_account(&s.p, group_by, from, to)
+                .await
+                .map_err(|err| {
+                    accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), s)
+                })?
+                .into_iter()
+                .map(|r| (r.name, r.amount.abs()))
+                .collect();
+        periods.push((from.format(label_format).to_string(), amounts));
     }
 
-    let entries: Vec<models::Entry> = sqlx::query_as::<_, models::Entry>(
-        "SELECT * FROM entry ORDER BY accounting_date DESC LIMIT 10 OFFSET $1",
-    )
-    .bind(((current_page - 1) * 10) as i64)
-    .fetch_all(&s.p)
-    .await
-    .unwrap();
+    // categories of the selected period first, then categories which disappeared
+    let mut categories: Vec<(String, BigDecimal)> = periods[0]
+        .1
+        .iter()
+        .map(|(c, a)| (c.clone(), a.clone()))
+        .collect();
+    categories.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut disappeared: Vec<String> = periods[1..]
+        .iter()
+        .flat_map(|(_, amounts)| amounts.keys())
+        .filter(|c| !periods[0].1.contains_key(*c))
+        .cloned()
+        .collect();
+    disappeared.sort();
+    disappeared.dedup();
 
-    #[derive(Serialize, Default)]
-    struct Pagination {
-        page: u32,
-        is_current: bool,
-        link: String,
+    #[derive(Serialize)]
+    struct Comparison {
+        amount: BigDecimal,
+        difference: BigDecimal,
+        difference_percent: Option<BigDecimal>,
+        increased: bool,
     }
 
-    #[derive(Serialize, Default)]
-    struct Ctx {
-        entries: Vec<models::Entry>,
-        pagination: Vec<Pagination>,
-        first_page: Option<String>,
-        last_page: Option<Pagination>,
-        previous_page: Option<String>,
-        next_page: Option<String>,
+    #[derive(Serialize)]
+    struct Row {
+        category: String,
+        amount: BigDecimal,
+        comparisons: Vec<Comparison>,
     }
 
-    let mut ctx = Ctx::default();
-    ctx.entries = entries;
-    ctx.pagination = (current_page as i32 - 3..=current_page as i32 + 3)
-        .filter(|p| *p > 0)
-        .filter(|p| *p <= max_page as i32)
-        .map(|p| Pagination {
-            page: p as u32,
-            is_current: p == current_page as i32,
-            link: format!("/api/entry?page={}", p),
+    let rows = categories
+        .into_iter()
+        .map(|(c, _)| c)
+        .chain(disappeared)
+        .map(|category| {
+            let amount = periods[0].1.get(&category).cloned().unwrap_or_default();
+            Row {
+                comparisons: periods[1..]
+                    .iter()
+                    .map(|(_, amounts)| {
+                        let earlier = amounts.get(&category).cloned().unwrap_or_default();
+                        let difference = &amount - &earlier;
+                        Comparison {
+                            difference_percent: (earlier != BigDecimal::default())
+                                .then(|| (&difference * BigDecimal::from(100) / &earlier).round(1)),
+                            increased: difference > BigDecimal::default(),
+                            difference,
+                            amount: earlier,
+                        }
+                    })
+                    .collect(),
+                category,
+                amount,
+            }
         })
         .collect();
 
-    if ctx.pagination.len() > 0 {
-        ctx.last_page = if ctx.pagination.last().unwrap().page == max_page {
-            None
-        } else {
-            Some(Pagination {
-                page: max_page,
-                is_current: false,
-                link: format!("/api/entry?page={}", max_page),
-            })
-        };
-        ctx.first_page = if ctx.pagination.first().unwrap().page == 1 {
-            None
-        } else {
-            Some(format!("/api/entry?page=1"))
-        };
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| accounts::AppMessage::new_error_notification(err, s))?;
 
-        ctx.next_page = if current_page == max_page {
-            None
-        } else {
-            Some(format!("/api/entry?page={}", current_page + 1))
-        };
-        ctx.previous_page = if current_page == 1 {
-            None
-        } else {
-            Some(format!("/api/entry?page={}", current_page - 1))
-        };
+    #[derive(Serialize)]
+    struct Ctx {
+        group_label: &'static str,
+        base_currency: String,
+        periods: Vec<String>,
+        rows: Vec<Row>,
     }
 
-    s.t.render("entry.hbs", &ctx).unwrap()
+    s.t.render(
+        "api.expenses.compare.hbs",
+        &Ctx {
+            group_label: match group_by {
+                GroupBy::Merchant => "Merchant",
+                _ => "Category",
+            },
+            base_currency,
+            periods: periods.into_iter().map(|(label, _)| label).collect(),
+            rows,
+        },
+    )
+    .map_err(|err| accounts::AppMessage::new_error_notification(err, s))
+}
+
+/// Filters of the transactions list, pages and sorting are read by `table::Query`.
+/// Filters are serialized back into the links of the table.
+#[derive(Deserialize, Serialize, Default, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct EntryQuery {
+    /// Searched in title, sender or receiver and address.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    q: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    /// Compared with the absolute amount, so it works for expenses and income.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_amount: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    currency: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_type: Option<String>,
+    /// `manual` or `imported`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    origin: Option<String>,
 }
 
-struct HtmlTemplate {
-    name: String,
+#[derive(Default)]
+struct EntryFilter {
+    pattern: Option<String>,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    min_amount: Option<BigDecimal>,
+    max_amount: Option<BigDecimal>,
+    category: Option<String>,
+    account_id: Option<uuid::Uuid>,
+    currency: Option<String>,
+    operation_type: Option<String>,
+    manual: Option<bool>,
 }
 
-impl IntoResponse for HtmlTemplate {
-    fn into_response(self) -> Response {
-        let path = format!("./src/front/public/{}", &self.name);
-        let mut handlebars = handlebars::Handlebars::new();
-        handlebars.register_template_file(&path, &path).unwrap();
-
-        match handlebars.render(&path, &format!("")) {
-            Ok(html) => Html(html).into_response(),
-            Err(err) => (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to render template. Error: {err}"),
-            )
-                .into_response(),
+/// Every condition is skipped when its parameter is NULL. The search expression
+/// is the same as in the `entry_search_idx` trigram index so the index is used.
+const ENTRY_FILTER: &str = r#"
+    ($1::text IS NULL OR (e.title || ' ' || e.sender_or_receiver || ' ' || e.address) ILIKE $1) AND
+    ($2::date IS NULL OR e.accounting_date >= $2) AND
+    ($3::date IS NULL OR e.accounting_date <= $3) AND
+    ($4::numeric IS NULL OR ABS(e.amount) >= $4) AND
+    ($5::numeric IS NULL OR ABS(e.amount) <= $5) AND
+    ($6::text IS NULL OR e.category = $6) AND
+    ($7::uuid IS NULL OR e.source_account IN (
+        SELECT reference FROM account_reference WHERE account_id = $7
+    )) AND
+    ($8::text IS NULL OR e.currency = $8) AND
+    ($9::text IS NULL OR e.operation_type = $9) AND
+    ($10::boolean IS NULL OR e.manual = $10)
+"#;
+
+impl EntryQuery {
+    /// Drops empty inputs and trims the rest.
+    fn normalize(mut self) -> Self {
+        for v in [
+            &mut self.q,
+            &mut self.from,
+            &mut self.to,
+            &mut self.min_amount,
+            &mut self.max_amount,
+            &mut self.category,
+            &mut self.account_id,
+            &mut self.currency,
+            &mut self.operation_type,
+            &mut self.origin,
+        ] {
+            *v = v
+                .take()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+        }
+        self
+    }
+
+    fn filter(&self) -> anyhow::Result<EntryFilter> {
+        let date = |v: &Option<String>, name: &str| {
+            v.as_deref()
+                .map(|d| d.parse::<chrono::NaiveDate>())
+                .transpose()
+                .with_context(|| BadRequest(format!("invalid {} date", name)))
+        };
+        let amount = |v: &Option<String>, name: &str| {
+            v.as_deref()
+                .map(|a| a.replace(',', ".").replace(' ', "").parse::<BigDecimal>())
+                .transpose()
+                .with_context(|| BadRequest(format!("invalid {} amount", name)))
+        };
+
+        Ok(EntryFilter {
+            // user input is matched literally, not as a LIKE pattern
+            pattern: self.q.as_deref().map(|q| {
+                format!(
+                    "%{}%",
+                    q.replace('\\', "\\\\")
+                        .replace('%', "\\%")
+                        .replace('_', "\\_")
+                )
+            }),
+            from: date(&self.from, "from")?,
+            to: date(&self.to, "to")?,
+            min_amount: amount(&self.min_amount, "minimum")?.map(|a| a.abs()),
+            max_amount: amount(&self.max_amount, "maximum")?.map(|a| a.abs()),
+            category: self.category.clone(),
+            account_id: self
+                .account_id
+                .as_deref()
+                .map(|id| id.parse::<uuid::Uuid>())
+                .transpose()
+                .context(BadRequest("invalid account".into()))?,
+            currency: self.currency.clone(),
+            operation_type: self.operation_type.clone(),
+            manual: match self.origin.as_deref() {
+                None => None,
+                Some("manual") => Some(true),
+                Some("imported") => Some(false),
+                Some(o) => {
+                    return Err(anyhow::anyhow!(BadRequest(format!(
+                        "unknown origin: {}",
+                        o
+                    ))))
+                }
+            },
+        })
+    }
+
+    /// Path of the list with the same filters, pages are added by the table.
+    fn api_path(&self) -> String {
+        match serde_urlencoded::to_string(self) {
+            Ok(filters) if !filters.is_empty() => format!("/api/entry?{}", filters),
+            _ => "/api/entry".to_string(),
         }
     }
 }
 
-// todo: this need to be refactored because data was inseeted incorectly, migrate db again
-async fn load(p: &Pool<Postgres>, s: impl io::Read) -> anyhow::Result<()> {
-    let mut rdr = csv::ReaderBuilder::new()
-        .has_headers(true)
-        .delimiter(b';')
-        .from_reader(s);
+/// Number of entries matching the filter and the requested page of them,
+/// `columns` are selected from `entry e`.
+async fn filtered_entries<T>(
+    p: &Pool<Postgres>,
+    f: &EntryFilter,
+    columns: &str,
+    t: &table::QueryNormalized,
+) -> sqlx::Result<(i64, Vec<T>)>
+where
+    T: for<'r> FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+{
+    let count: i64 = sqlx::query_scalar(&format!(
+        "SELECT COUNT(*) FROM entry e WHERE {}",
+        ENTRY_FILTER
+    ))
+    .bind(&f.pattern)
+    .bind(f.from)
+    .bind(f.to)
+    .bind(&f.min_amount)
+    .bind(&f.max_amount)
+    .bind(&f.category)
+    .bind(f.account_id)
+    .bind(&f.currency)
+    .bind(&f.operation_type)
+    .bind(f.manual)
+    .fetch_one(p)
+    .await?;
+
+    let entries = sqlx::query_as::<_, T>(&format!(
+        r#"
+        SELECT {}
+        FROM entry e
+        WHERE {}
+        ORDER BY {}
+        LIMIT $11
+        OFFSET $12
+        "#,
+        columns,
+        ENTRY_FILTER,
+        t.order_by("accounting_date DESC, id DESC")
+    ))
+    .bind(&f.pattern)
+    .bind(f.from)
+    .bind(f.to)
+    .bind(&f.min_amount)
+    .bind(&f.max_amount)
+    .bind(&f.category)
+    .bind(f.account_id)
+    .bind(&f.currency)
+    .bind(&f.operation_type)
+    .bind(f.manual)
+    .bind(t.limit())
+    .bind(t.offset())
+    .fetch_all(p)
+    .await?;
+
+    Ok((count, entries))
+}
 
-    let mut count = 0;
+#[axum::debug_handler]
+async fn api_entry(
+    State(s): State<AppState>,
+    Query(t): Query<table::Query>,
+    Query(query): Query<EntryQuery>,
+) -> Result<Response, accounts::AppMessage> {
+    #[derive(sqlx::FromRow, Serialize, Default)]
+    struct Record {
+        id: uuid::Uuid,
+        accounting_date: chrono::NaiveDate,
+        sender_or_receiver: String,
+        title: String,
+        amount: BigDecimal,
+        currency: String,
+        category: String,
+    }
 
-    for result in rdr.records() {
-        let record = result?;
-        let insert_query = format!(
-            r#"INSERT INTO entry (
-                 accounting_date,
-                 currency_date,
-                 sender_or_receiver,
-                 address,
-                 source_account,
-                 destination_account,
-                 title,
-                 amount,
-                 currency, 
-                 reference_number,
-                 operation_type,
-                 category
-            ) VALUES (
-                to_date($1, 'DD.MM.YYYY'),
-                to_date($2, 'DD.MM.YYYY'),
-                $3,
-                $4,
-                $5,
-                $6,
-                $7,
-                $8,
-                $9,
-                $10,
-                $11,
-                $12
-            );"#,
-        );
+    let t = t
+        .noramlize::<Record>()
+        .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?;
+    let query = query.normalize();
+    let f = query
+        .filter()
+        .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?;
+
+    let (count, entries) = filtered_entries::<Record>(
+        &s.p,
+        &f,
+        "e.id, e.accounting_date, e.sender_or_receiver, e.title, e.amount, e.currency, e.category",
+        &t,
+    )
+    .await
+    .map_err(|err| accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), &s))?;
+
+    let table = table::TableComponent::new(entries, count, query.api_path(), t)
+        .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?
+        .with_columns(&[
+            "accounting_date",
+            "sender_or_receiver",
+            "title",
+            "amount",
+            "category",
+        ])
+        .with_label("accounting_date", "Date")
+        .with_label("sender_or_receiver", "Entity")
+        .with_label("title", "Title")
+        .with_label("amount", "Amount")
+        .with_label("category", "Category")
+        .with_formatter("accounting_date", table::date)
+        .with_formatter("amount", table::money)
+        .with_row_link(|e| format!("/details?entry_id={}", e.id))
+        .with_selection("entry_id", |e| e.id.to_string())
+        .with_target("entries");
 
-        let amount = record[7]
-            .replace(",", ".")
-            .replace(" ", "")
-            .parse::<f64>()
-            .with_context(|| format!("value: {}", &record[7]))?;
-
-        let err = match sqlx::query(&insert_query)
-            .bind(&record[0])
-            .bind(&record[1])
-            .bind(&record[2])
-            .bind(&record[3])
-            .bind(&record[4])
-            .bind(&record[5])
-            .bind(&record[6])
-            .bind(amount)
-            .bind(&record[8])
-            .bind(&record[9])
-            .bind(&record[10])
-            .bind(&record[11])
-            .execute(p)
-            .await
-        {
-            Ok(_) => {
-                count += 1;
-                Ok(())
-            }
-            Err(err) => {
-                if let Some(err) = err.as_database_error() {
-                    if err.is_unique_violation() {
-                        log::warn!(
-                            "cannnt load entry: violation for entry {:?}: {}",
-                            record,
-                            err
-                        );
-                        continue;
-                    }
-                }
+    #[derive(Serialize)]
+    struct Ctx {
+        data: table::TableComponent<Record>,
+    }
 
-                log::error!("cannot insert: {}: {}", insert_query, err);
+    let ret =
+        s.t.render("component.table.hbs", &Ctx { data: table })
+            .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?;
+    Ok(ret)
+}
 
-                return Err(anyhow::anyhow!(err));
-            }
-        };
+/// Filter bar of the transactions list with values to choose from.
+#[axum::debug_handler]
+async fn api_entry_filters(State(s): State<AppState>) -> Result<Response, accounts::AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Account {
+        id: uuid::Uuid,
+        name: String,
+    }
 
-        if let Err(err) = err {
-            return err;
-        }
+    let accounts = sqlx::query_as::<_, Account>("SELECT id, name FROM account ORDER BY name ASC")
+        .fetch_all(&s.p)
+        .await
+        .map_err(|err| accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), &s))?;
+
+    let distinct = |column: &str| {
+        format!(
+            "SELECT DISTINCT {column} FROM entry WHERE {column} <> '' ORDER BY {column} ASC",
+            column = column
+        )
+    };
+    let mut values = Vec::new();
+    for column in ["category", "currency", "operation_type"] {
+        let v: Vec<String> = sqlx::query_scalar(&distinct(column))
+            .fetch_all(&s.p)
+            .await
+            .map_err(|err| {
+                accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), &s)
+            })?;
+        values.push(v);
     }
+    let [categories, currencies, operation_types]: [Vec<String>; 3] =
+        values.try_into().unwrap_or_default();
 
-    log::info!("{} records were loaded to db", count);
+    #[derive(Serialize)]
+    struct Ctx {
+        accounts: Vec<Account>,
+        categories: Vec<String>,
+        currencies: Vec<String>,
+        operation_types: Vec<String>,
+    }
 
-    Ok(())
+    let ret =
+        s.t.render(
+            "entry.filters.hbs",
+            &Ctx {
+                accounts,
+                categories,
+                currencies,
+                operation_types,
+            },
+        )
+        .map_err(|err| accounts::AppMessage::new_error_notification(anyhow::anyhow!(err), &s))?;
+    Ok(ret)
 }
 
+/// Imports the uploaded bank statements like `budgetv2 import`.
 #[axum_macros::debug_handler]
 async fn api_upload(
     State(s): State<AppState>,
     mut multipart: Multipart,
-) -> Result<Redirect, (StatusCode, String)> {
-    while let Ok(Some(m)) = multipart.next_field().await {
-        let file_name = m.file_name().unwrap();
-        log::info!("file_name={}", file_name);
+) -> Result<Response, accounts::AppMessage> {
+    let multipart_error = |err: MultipartError| {
+        accounts::AppMessage::new_error_notification(
+            anyhow::anyhow!(BadRequest(err.body_text())),
+            &s,
+        )
+    };
 
-        let name = m.name().unwrap();
-        log::info!("name={}", name);
+    let mut count = 0;
+    while let Some(m) = multipart.next_field().await.map_err(multipart_error)? {
+        log::info!(
+            "file_name={} name={}",
+            m.file_name().unwrap_or_default(),
+            m.name().unwrap_or_default()
+        );
 
-        let bytes = m.bytes().await.unwrap();
+        let bytes = m.bytes().await.map_err(multipart_error)?;
 
-        match load(&s.p, bytes.as_ref()).await {
-            Ok(_) => (),
-            Err(err) => log::error!("cannot load file: {}", err),
-        }
+        count += import::load(&s.p, import::Format::Mbank, bytes.as_ref(), None)
+            .await
+            .map_err(|err| accounts::AppMessage::new_error_notification(err, &s))?;
+    }
+
+    import::after_load(&s.p).await;
+
+    let mut ret =
+        accounts::AppMessage::new_info_notification(format!("{} entries imported", count), &s)
+            .into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("entries-updated"));
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db;
+
+    async fn insert_entry(p: &Pool<Postgres>, category: &str, amount: i64, currency: &str) {
+        sqlx::query(
+            r#"
+            INSERT INTO entry (
+                accounting_date, currency_date, sender_or_receiver, address, source_account,
+                destination_account, title, amount, currency, reference_number, operation_type,
+                category
+            ) VALUES (
+                '2024-03-10', '2024-03-10', 'shop', '', 'ACC1', '', 'title', $1, $2,
+                gen_random_uuid()::text, '', $3
+            )
+            "#,
+        )
+        .bind(amount)
+        .bind(currency)
+        .bind(category)
+        .execute(p)
+        .await
+        .unwrap();
     }
 
-    return Err((StatusCode::NO_CONTENT, format!("sucess")));
+    #[tokio::test]
+    async fn sums_leave_out_groups_without_exchange_rate() {
+        let Some(db) = test_db::create().await else {
+            return;
+        };
+        let p = &db.pool;
+        let account_id: uuid::Uuid =
+            sqlx::query_scalar("INSERT INTO account (name) VALUES ('main') RETURNING id")
+                .fetch_one(p)
+                .await
+                .unwrap();
+        sqlx::query("INSERT INTO account_reference (account_id, reference) VALUES ($1, 'ACC1')")
+            .bind(account_id)
+            .execute(p)
+            .await
+            .unwrap();
+        insert_entry(p, "food", -10, currency::DEFAULT_BASE_CURRENCY).await;
+        insert_entry(p, "food", -5, "XAU").await;
+        insert_entry(p, "gold", -7, "XAU").await;
+
+        let from = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let to = chrono::NaiveDate::from_ymd_opt(2024, 4, 1).unwrap();
+        let groups = sum_by_account(
+            p,
+            &account_id.to_string(),
+            GroupBy::Category,
+            true,
+            from,
+            to,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            groups
+                .into_iter()
+                .map(|g| (g.name, g.amount))
+                .collect::<Vec<_>>(),
+            [("food".to_string(), BigDecimal::from(-10))]
+        );
+
+        let months = reports::expenses_by_category_and_month(p, from, from)
+            .await
+            .unwrap();
+        assert_eq!(
+            months
+                .into_iter()
+                .map(|m| (m.category, m.amount))
+                .collect::<Vec<_>>(),
+            [("food".to_string(), BigDecimal::from(10))]
+        );
+
+        db.remove().await;
+    }
 }
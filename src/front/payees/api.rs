@@ -0,0 +1,249 @@
+use crate::{
+    currency,
+    error::BadRequest,
+    front::{accounts::AppMessage, reports, AppState},
+    payees,
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Form, Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/", axum::routing::post(post))
+        .route("/", axum::routing::delete(delete))
+        .route("/patterns", axum::routing::post(post_pattern))
+        .route("/patterns", axum::routing::delete(delete_pattern))
+        .route("/match", axum::routing::post(run_match))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        id: Uuid,
+        name: String,
+        patterns: i64,
+        total_spent: BigDecimal,
+        visits: i64,
+        last_visit: Option<NaiveDate>,
+    }
+
+    let payees = sqlx::query_as::<_, Record>(&format!(
+        r#"
+        SELECT
+            p.id,
+            p.name,
+            (SELECT COUNT(*) FROM payee_pattern pp WHERE pp.payee_id = p.id) AS patterns,
+            COALESCE(-SUM({amount}) FILTER (WHERE e.amount < 0), 0) AS total_spent,
+            COUNT(e.id) FILTER (WHERE e.amount < 0) AS visits,
+            MAX(e.accounting_date) AS last_visit
+        FROM payee p
+        LEFT JOIN entry e ON e.payee_id = p.id
+        GROUP BY p.id, p.name
+        ORDER BY total_spent DESC, p.name ASC
+        "#,
+        amount = reports::BASE_AMOUNT
+    ))
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Unmatched {
+        sender_or_receiver: String,
+        total_spent: BigDecimal,
+        visits: i64,
+    }
+
+    // biggest counterparties without a payee, good candidates for new ones,
+    // entries without an exchange rate are left out
+    let unmatched = sqlx::query_as::<_, Unmatched>(&format!(
+        r#"
+        SELECT
+            e.sender_or_receiver,
+            -SUM({amount}) AS total_spent,
+            COUNT(*) AS visits
+        FROM entry e
+        WHERE e.payee_id IS NULL AND e.amount < 0 AND {amount} IS NOT NULL
+        GROUP BY e.sender_or_receiver
+        ORDER BY total_spent DESC
+        LIMIT 10
+        "#,
+        amount = reports::BASE_AMOUNT
+    ))
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        payees: Vec<Record>,
+        unmatched: Vec<Unmatched>,
+        base_currency: String,
+    }
+
+    let ret =
+        s.t.render(
+            "api.payees.get.hbs",
+            &Ctx {
+                payees,
+                unmatched,
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct PayeeForm {
+    name: String,
+    kind: String,
+    pattern: String,
+}
+
+#[axum::debug_handler]
+async fn post(State(s): State<AppState>, Form(f): Form<PayeeForm>) -> Result<Response, AppMessage> {
+    if f.name.trim().is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("name cannot be empty".into())),
+            &s,
+        ));
+    }
+    if !payees::is_valid_kind(&f.kind) {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(format!("invalid pattern kind: {}", f.kind))),
+            &s,
+        ));
+    }
+
+    let mut tx =
+        s.p.begin()
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let id: Uuid = sqlx::query_scalar("INSERT INTO payee (name) VALUES ($1) RETURNING id")
+        .bind(f.name.trim())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    if !f.pattern.trim().is_empty() {
+        sqlx::query("INSERT INTO payee_pattern (payee_id, kind, pattern) VALUES ($1, $2, $3)")
+            .bind(id)
+            .bind(&f.kind)
+            .bind(f.pattern.trim())
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    }
+
+    tx.commit()
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    relink(&s, "payee added").await
+}
+
+#[derive(Deserialize)]
+struct IdQuery {
+    id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn delete(
+    State(s): State<AppState>,
+    Query(q): Query<IdQuery>,
+) -> Result<Response, AppMessage> {
+    // entries are unlinked by ON DELETE SET NULL
+    sqlx::query("DELETE FROM payee WHERE id = $1")
+        .bind(q.id)
+        .execute(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    relink(&s, "payee removed").await
+}
+
+#[derive(Deserialize)]
+struct PatternForm {
+    payee_id: Uuid,
+    kind: String,
+    pattern: String,
+}
+
+#[axum::debug_handler]
+async fn post_pattern(
+    State(s): State<AppState>,
+    Form(f): Form<PatternForm>,
+) -> Result<Response, AppMessage> {
+    if f.pattern.trim().is_empty() {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest("pattern cannot be empty".into())),
+            &s,
+        ));
+    }
+    if !payees::is_valid_kind(&f.kind) {
+        return Err(AppMessage::new_error_notification(
+            anyhow!(BadRequest(format!("invalid pattern kind: {}", f.kind))),
+            &s,
+        ));
+    }
+
+    sqlx::query("INSERT INTO payee_pattern (payee_id, kind, pattern) VALUES ($1, $2, $3)")
+        .bind(f.payee_id)
+        .bind(&f.kind)
+        .bind(f.pattern.trim())
+        .execute(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    relink(&s, "pattern added").await
+}
+
+#[axum::debug_handler]
+async fn delete_pattern(
+    State(s): State<AppState>,
+    Query(q): Query<IdQuery>,
+) -> Result<Response, AppMessage> {
+    sqlx::query("DELETE FROM payee_pattern WHERE id = $1")
+        .bind(q.id)
+        .execute(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    relink(&s, "pattern removed").await
+}
+
+#[axum::debug_handler]
+async fn run_match(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    relink(&s, "payees matched").await
+}
+
+/// Links entries again after rules changed and tells the page to reload.
+async fn relink(s: &AppState, msg: &str) -> Result<Response, AppMessage> {
+    let linked = payees::link_entries(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, s))?;
+
+    let mut ret =
+        AppMessage::new_info_notification(format!("{}, {} entries updated", msg, linked), s)
+            .into_response();
+    ret.headers_mut()
+        .insert("HX-Trigger", HeaderValue::from_static("payees-updated"));
+    Ok(ret)
+}
@@ -0,0 +1,152 @@
+pub mod api;
+
+use super::{accounts::AppMessage, reports, AppState};
+use crate::{currency, error::NotFound};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    response::Response,
+    Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/details", axum::routing::get(get_details))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let res =
+        s.t.render("payees.get.hbs", &())
+            .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
+
+#[derive(Deserialize)]
+struct DetailsQuery {
+    payee_id: Uuid,
+}
+
+#[axum::debug_handler]
+async fn get_details(
+    State(s): State<AppState>,
+    Query(q): Query<DetailsQuery>,
+) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Payee {
+        id: Uuid,
+        name: String,
+        total_spent: BigDecimal,
+        total_received: BigDecimal,
+        visits: i64,
+        first_visit: Option<NaiveDate>,
+        last_visit: Option<NaiveDate>,
+    }
+
+    let payee = sqlx::query_as::<_, Payee>(&format!(
+        r#"
+        SELECT
+            p.id,
+            p.name,
+            COALESCE(-SUM({amount}) FILTER (WHERE e.amount < 0), 0) AS total_spent,
+            COALESCE(SUM({amount}) FILTER (WHERE e.amount > 0), 0) AS total_received,
+            COUNT(e.id) FILTER (WHERE e.amount < 0) AS visits,
+            MIN(e.accounting_date) AS first_visit,
+            MAX(e.accounting_date) AS last_visit
+        FROM payee p
+        LEFT JOIN entry e ON e.payee_id = p.id
+        WHERE p.id = $1
+        GROUP BY p.id, p.name
+        "#,
+        amount = reports::BASE_AMOUNT
+    ))
+    .bind(q.payee_id)
+    .fetch_optional(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?
+    .ok_or(anyhow!(NotFound("payee not found".into())))
+    .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Pattern {
+        id: Uuid,
+        kind: String,
+        pattern: String,
+    }
+
+    let patterns = sqlx::query_as::<_, Pattern>(
+        "SELECT id, kind, pattern FROM payee_pattern WHERE payee_id = $1 ORDER BY kind, pattern",
+    )
+    .bind(q.payee_id)
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        id: Uuid,
+        accounting_date: NaiveDate,
+        sender_or_receiver: String,
+        title: String,
+        amount: BigDecimal,
+        currency: String,
+        category: String,
+    }
+
+    let entries = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            id,
+            accounting_date,
+            sender_or_receiver,
+            title,
+            amount,
+            currency,
+            category
+        FROM entry
+        WHERE payee_id = $1
+        ORDER BY accounting_date DESC
+        "#,
+    )
+    .bind(q.payee_id)
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        payee: Payee,
+        base_currency: String,
+        average_spent: BigDecimal,
+        patterns: Vec<Pattern>,
+        entries: Vec<Record>,
+    }
+
+    let average_spent = match payee.visits {
+        0 => BigDecimal::default(),
+        v => (&payee.total_spent / BigDecimal::from(v)).round(2),
+    };
+
+    let res =
+        s.t.render(
+            "payees.details.hbs",
+            &Ctx {
+                payee,
+                base_currency,
+                average_spent,
+                patterns,
+                entries,
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
@@ -0,0 +1,447 @@
+use std::collections::BTreeMap;
+
+use super::{
+    expenses_by_category_and_month, month_range, monthly_csv, monthly_summary, parse_month,
+    MonthSummary, REPORTABLE_ENTRY,
+};
+use crate::{
+    currency,
+    error::BadRequest,
+    front::{
+        accounts::AppMessage,
+        components::sankey::{Flow, Sankey},
+        sum_by_account, AppState, GroupBy,
+    },
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Router,
+};
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Biggest income sources and expense categories shown separately in the Sankey diagram,
+/// the rest is grouped together.
+const MAX_SANKEY_NODES: usize = 8;
+
+pub const DEFAULT_TREND_MONTHS: u32 = 6;
+const MIN_TREND_MONTHS: u32 = 2;
+const MAX_TREND_MONTHS: u32 = 24;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/monthly", axum::routing::get(get_monthly))
+        .route("/monthly.csv", axum::routing::get(get_monthly_csv))
+        .route("/monthly/entries", axum::routing::get(get_monthly_entries))
+        .route("/trends", axum::routing::get(get_trends))
+        .route("/sankey", axum::routing::get(get_sankey))
+}
+
+#[derive(Deserialize)]
+struct RangeQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+struct Totals {
+    income: BigDecimal,
+    expenses: BigDecimal,
+    net_savings: BigDecimal,
+    savings_rate: Option<BigDecimal>,
+}
+
+impl Totals {
+    fn new(months: &[MonthSummary]) -> Self {
+        let mut totals = months.iter().fold(Totals::default(), |mut t, m| {
+            t.income += &m.income;
+            t.expenses += &m.expenses;
+            t.net_savings += &m.net_savings;
+            t
+        });
+        if totals.income != BigDecimal::default() {
+            totals.savings_rate =
+                Some((&totals.net_savings * BigDecimal::from(100) / &totals.income).round(1));
+        }
+        totals
+    }
+}
+
+#[axum::debug_handler]
+async fn get_monthly(
+    State(s): State<AppState>,
+    Query(q): Query<RangeQuery>,
+) -> Result<Response, AppMessage> {
+    let (from, to) = month_range(q.from.as_deref(), q.to.as_deref())
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let months = monthly_summary(&s.p, from, to)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        totals: Totals,
+        months: Vec<MonthSummary>,
+        csv_link: String,
+        base_currency: String,
+    }
+
+    let ret =
+        s.t.render(
+            "api.reports.monthly.hbs",
+            &Ctx {
+                totals: Totals::new(&months),
+                months,
+                csv_link: format!(
+                    "/api/reports/monthly.csv?from={}&to={}",
+                    from.format("%Y-%m"),
+                    to.format("%Y-%m")
+                ),
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[axum::debug_handler]
+async fn get_monthly_csv(
+    State(s): State<AppState>,
+    Query(q): Query<RangeQuery>,
+) -> Result<Response, AppMessage> {
+    let (from, to) = month_range(q.from.as_deref(), q.to.as_deref())
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let months = monthly_summary(&s.p, from, to)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let csv = monthly_csv(&months).map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"monthly_{}_{}.csv\"",
+                    from.format("%Y-%m"),
+                    to.format("%Y-%m")
+                ),
+            ),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+struct MonthlyEntriesQuery {
+    month: String,
+    kind: String,
+}
+
+#[axum::debug_handler]
+async fn get_monthly_entries(
+    State(s): State<AppState>,
+    Query(q): Query<MonthlyEntriesQuery>,
+) -> Result<Response, AppMessage> {
+    let from = parse_month(&q.month).map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    let to = from
+        .checked_add_months(Months::new(1))
+        .ok_or(anyhow!(BadRequest("invalid month".into())))
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    let sign = match q.kind.as_str() {
+        "income" => "e.amount > 0",
+        "expenses" => "e.amount < 0",
+        _ => {
+            return Err(AppMessage::new_error_notification(
+                anyhow!(BadRequest(format!("unknown kind '{}'", q.kind))),
+                &s,
+            ))
+        }
+    };
+
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        id: Uuid,
+        accounting_date: NaiveDate,
+        sender_or_receiver: String,
+        title: String,
+        amount: BigDecimal,
+        currency: String,
+        category: String,
+    }
+
+    let entries = sqlx::query_as::<_, Record>(&format!(
+        r#"
+        SELECT
+            e.id,
+            e.accounting_date,
+            e.sender_or_receiver,
+            e.title,
+            e.amount,
+            e.currency,
+            e.category
+        FROM entry e
+        WHERE
+            e.accounting_date >= $1 AND
+            e.accounting_date < $2 AND
+            {} AND
+            {}
+        ORDER BY e.accounting_date ASC, e.amount ASC
+        "#,
+        sign, REPORTABLE_ENTRY
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        month: String,
+        kind: String,
+        entries: Vec<Record>,
+    }
+
+    let ret =
+        s.t.render(
+            "api.reports.entries.hbs",
+            &Ctx {
+                month: q.month,
+                kind: q.kind,
+                entries,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[derive(Deserialize)]
+struct TrendsQuery {
+    months: Option<u32>,
+}
+
+#[axum::debug_handler]
+async fn get_trends(
+    State(s): State<AppState>,
+    Query(q): Query<TrendsQuery>,
+) -> Result<Response, AppMessage> {
+    let n = q
+        .months
+        .unwrap_or(DEFAULT_TREND_MONTHS)
+        .clamp(MIN_TREND_MONTHS, MAX_TREND_MONTHS);
+
+    let to = chrono::Local::now()
+        .date_naive()
+        .with_day(1)
+        .ok_or(anyhow!("invalid current date"))
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    let months: Vec<NaiveDate> = (0..n)
+        .rev()
+        .filter_map(|i| to.checked_sub_months(Months::new(i)))
+        .collect();
+    let from = *months.first().unwrap_or(&to);
+
+    let records = expenses_by_category_and_month(&s.p, from, to)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let mut matrix: BTreeMap<String, Vec<BigDecimal>> = BTreeMap::new();
+    for r in records {
+        let Some(i) = months.iter().position(|m| *m == r.month) else {
+            continue;
+        };
+        matrix
+            .entry(r.category)
+            .or_insert_with(|| vec![BigDecimal::zero(); months.len()])[i] = r.amount;
+    }
+
+    #[derive(Serialize)]
+    struct Cell {
+        amount: BigDecimal,
+        height_ratio: String, // from 0 to 100
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        category: String,
+        cells: Vec<Cell>,
+        total: BigDecimal,
+        average: BigDecimal,
+        min: BigDecimal,
+        max: BigDecimal,
+        change: BigDecimal,
+        change_percent: Option<BigDecimal>,
+        increased: bool,
+    }
+
+    let mut rows: Vec<Row> = matrix
+        .into_iter()
+        .map(|(category, amounts)| {
+            let total: BigDecimal = amounts.iter().sum();
+            let min = amounts.iter().min().cloned().unwrap_or_default();
+            let max = amounts.iter().max().cloned().unwrap_or_default();
+            let last = amounts.last().cloned().unwrap_or_default();
+            let previous = amounts
+                .len()
+                .checked_sub(2)
+                .and_then(|i| amounts.get(i))
+                .cloned()
+                .unwrap_or_default();
+            let change = &last - &previous;
+            Row {
+                category,
+                cells: amounts
+                    .iter()
+                    .map(|a| Cell {
+                        amount: a.clone(),
+                        height_ratio: if max.is_zero() {
+                            "0".to_string()
+                        } else {
+                            (a / &max * BigDecimal::from(100)).round(0).to_string()
+                        },
+                    })
+                    .collect(),
+                average: (&total / BigDecimal::from(amounts.len() as i64)).round(2),
+                total,
+                min,
+                max,
+                change_percent: (!previous.is_zero())
+                    .then(|| (&change * BigDecimal::from(100) / &previous).round(1)),
+                increased: change > BigDecimal::zero(),
+                change,
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| b.total.cmp(&a.total));
+
+    let base_currency = currency::base_currency(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        months: Vec<String>,
+        rows: Vec<Row>,
+        base_currency: String,
+    }
+
+    let ret =
+        s.t.render(
+            "api.reports.trends.hbs",
+            &Ctx {
+                months: months
+                    .iter()
+                    .map(|m| m.format("%Y-%m").to_string())
+                    .collect(),
+                rows,
+                base_currency,
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[axum::debug_handler]
+async fn get_sankey(
+    State(s): State<AppState>,
+    Query(q): Query<RangeQuery>,
+) -> Result<Response, AppMessage> {
+    let (from, to) = month_range(q.from.as_deref(), q.to.as_deref())
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+    let to = to
+        .checked_add_months(Months::new(1))
+        .ok_or(anyhow!("invalid report range"))
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    #[derive(sqlx::FromRow)]
+    struct Account {
+        id: Uuid,
+        name: String,
+    }
+
+    let accounts = sqlx::query_as::<_, Account>("SELECT id, name FROM account ORDER BY name ASC")
+        .fetch_all(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    let mut flows = Vec::new();
+    for a in accounts {
+        let id = a.id.to_string();
+        let income = sum_by_account(&s.p, &id, GroupBy::SenderOrReceiver, false, from, to)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+        let expenses = sum_by_account(&s.p, &id, GroupBy::Category, true, from, to)
+            .await
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+        let total_income: BigDecimal = income.iter().map(|r| &r.amount).sum();
+        let total_expenses: BigDecimal = expenses.iter().map(|r| r.amount.abs()).sum();
+
+        for (i, r) in income.iter().enumerate() {
+            let source = match i < MAX_SANKEY_NODES {
+                true => r.name.clone(),
+                false => "Other income".to_string(),
+            };
+            flows.push(Flow {
+                from: (0, source),
+                to: (1, a.name.clone()),
+                value: r.amount.to_f64().unwrap_or(0.0),
+            });
+        }
+        if total_expenses > total_income {
+            flows.push(Flow {
+                from: (0, "From balance".to_string()),
+                to: (1, a.name.clone()),
+                value: (&total_expenses - &total_income).to_f64().unwrap_or(0.0),
+            });
+        }
+
+        for (i, r) in expenses.iter().enumerate() {
+            let category = match i < MAX_SANKEY_NODES {
+                true => r.name.clone(),
+                false => "Other expenses".to_string(),
+            };
+            flows.push(Flow {
+                from: (1, a.name.clone()),
+                to: (2, category),
+                value: r.amount.abs().to_f64().unwrap_or(0.0),
+            });
+        }
+        if total_income > total_expenses {
+            flows.push(Flow {
+                from: (1, a.name.clone()),
+                to: (2, "Savings".to_string()),
+                value: (&total_income - &total_expenses).to_f64().unwrap_or(0.0),
+            });
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Ctx {
+        sankey: Sankey,
+    }
+
+    let ret =
+        s.t.render(
+            "api.reports.sankey.hbs",
+            &Ctx {
+                sankey: Sankey::new(3, flows),
+            },
+        )
+        .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
@@ -0,0 +1,256 @@
+pub mod api;
+
+use super::{accounts::AppMessage, AppState};
+use crate::error::BadRequest;
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Datelike, Months, NaiveDate};
+use serde::Serialize;
+use sqlx::{Pool, Postgres};
+
+/// Condition for entries which are counted in reports, expects `entry` aliased as `e`.
+/// Transfers between own accounts are neither income nor expense. Entries can also be
+/// excluded or marked as transfers by the user.
+pub const REPORTABLE_ENTRY: &str = r#"
+    NOT e.excluded_from_reports AND
+    NOT e.is_transfer AND
+    NOT (
+        e.source_account IN (SELECT reference FROM account_reference) AND
+        e.destination_account IN (SELECT reference FROM account_reference)
+    )
+"#;
+
+/// Amount of the entry (aliased as `e`) in the base currency, converted with the
+/// exchange rate of its accounting date.
+pub const BASE_AMOUNT: &str = "to_base_currency(e.amount, e.currency, e.accounting_date)";
+
+const DEFAULT_REPORT_MONTHS: u32 = 12;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/monthly", axum::routing::get(get_monthly))
+        .route("/trends", axum::routing::get(get_trends))
+        .route("/sankey", axum::routing::get(get_sankey))
+}
+
+#[derive(Serialize, Debug)]
+pub struct MonthSummary {
+    pub month: String,
+    pub income: BigDecimal,
+    pub expenses: BigDecimal,
+    pub net_savings: BigDecimal,
+    /// Percent of income which was not spent, none when there was no income.
+    pub savings_rate: Option<BigDecimal>,
+}
+
+/// Parses `YYYY-MM` into the first day of the month.
+pub fn parse_month(month: &str) -> anyhow::Result<NaiveDate> {
+    NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").map_err(|err| {
+        anyhow!(BadRequest(format!(
+            "invalid month '{}', expected YYYY-MM: {}",
+            month, err
+        )))
+    })
+}
+
+/// Resolves optional `YYYY-MM` range, by default the last twelve months including the current one.
+pub fn month_range(from: Option<&str>, to: Option<&str>) -> anyhow::Result<(NaiveDate, NaiveDate)> {
+    let to = match to.filter(|m| !m.trim().is_empty()) {
+        Some(m) => parse_month(m)?,
+        None => chrono::Local::now()
+            .date_naive()
+            .with_day(1)
+            .ok_or(anyhow!("invalid current date"))?,
+    };
+    let from = match from.filter(|m| !m.trim().is_empty()) {
+        Some(m) => parse_month(m)?,
+        None => to
+            .checked_sub_months(Months::new(DEFAULT_REPORT_MONTHS - 1))
+            .ok_or(anyhow!("invalid report range"))?,
+    };
+    if from > to {
+        return Err(anyhow!(BadRequest(
+            "report range starts after it ends".into()
+        )));
+    }
+    Ok((from, to))
+}
+
+/// Income, expenses and savings for every month between `from` and `to` (both inclusive).
+pub async fn monthly_summary(
+    p: &Pool<Postgres>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> anyhow::Result<Vec<MonthSummary>> {
+    #[derive(sqlx::FromRow)]
+    struct Record {
+        month: NaiveDate,
+        income: BigDecimal,
+        expenses: BigDecimal,
+    }
+
+    let records: Vec<Record> = sqlx::query_as(&format!(
+        r#"
+        SELECT
+            m.month::date AS month,
+            COALESCE(SUM({amount}) FILTER (WHERE e.amount > 0), 0) AS income,
+            COALESCE(-SUM({amount}) FILTER (WHERE e.amount < 0), 0) AS expenses
+        FROM generate_series($1::date, $2::date, '1 month') AS m(month)
+        LEFT JOIN entry e ON
+            date_trunc('month', e.accounting_date) = m.month AND
+            {reportable}
+        GROUP BY m.month
+        ORDER BY m.month ASC
+        "#,
+        amount = BASE_AMOUNT,
+        reportable = REPORTABLE_ENTRY
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(p)
+    .await?;
+
+    Ok(records
+        .into_iter()
+        .map(|r| {
+            let net_savings = &r.income - &r.expenses;
+            MonthSummary {
+                month: r.month.format("%Y-%m").to_string(),
+                savings_rate: (!r.income.is_zero())
+                    .then(|| (&net_savings * BigDecimal::from(100) / &r.income).round(1)),
+                net_savings,
+                income: r.income,
+                expenses: r.expenses,
+            }
+        })
+        .collect())
+}
+
+/// Monthly summary as CSV with a header, amounts in the base currency.
+pub fn monthly_csv(months: &[MonthSummary]) -> anyhow::Result<Vec<u8>> {
+    let mut wtr = csv::Writer::from_writer(vec![]);
+    wtr.write_record(["month", "income", "expenses", "net_savings", "savings_rate"])?;
+    for m in months {
+        wtr.write_record([
+            m.month.clone(),
+            m.income.round(2).to_string(),
+            m.expenses.round(2).to_string(),
+            m.net_savings.round(2).to_string(),
+            m.savings_rate
+                .as_ref()
+                .map(|r| r.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    Ok(wtr.into_inner()?)
+}
+
+#[derive(sqlx::FromRow, Debug)]
+pub struct CategoryMonth {
+    pub category: String,
+    pub month: NaiveDate,
+    /// Spent amount as a positive number.
+    pub amount: BigDecimal,
+}
+
+/// Expenses per category and month between `from` and `to` (both inclusive),
+/// months without expenses in a category are not returned. Entries without an
+/// exchange rate are left out.
+pub async fn expenses_by_category_and_month(
+    p: &Pool<Postgres>,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> anyhow::Result<Vec<CategoryMonth>> {
+    let to = to
+        .checked_add_months(Months::new(1))
+        .ok_or(anyhow!("invalid report range"))?;
+
+    Ok(sqlx::query_as(&format!(
+        r#"
+        SELECT
+            e.category,
+            date_trunc('month', e.accounting_date)::date AS month,
+            -SUM({amount}) AS amount
+        FROM entry e
+        WHERE
+            e.accounting_date >= $1 AND
+            e.accounting_date < $2 AND
+            e.amount < 0 AND
+            {amount} IS NOT NULL AND
+            {reportable}
+        GROUP BY e.category, month
+        ORDER BY e.category ASC, month ASC
+        "#,
+        amount = BASE_AMOUNT,
+        reportable = REPORTABLE_ENTRY
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(p)
+    .await?)
+}
+
+#[axum::debug_handler]
+async fn get_monthly(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let (from, to) = month_range(None, None).map_err(|err| AppMessage::new_error(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        from: String,
+        to: String,
+    }
+
+    let res =
+        s.t.render(
+            "reports.monthly.get.hbs",
+            &Ctx {
+                from: from.format("%Y-%m").to_string(),
+                to: to.format("%Y-%m").to_string(),
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
+
+#[axum::debug_handler]
+async fn get_trends(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(Serialize)]
+    struct Ctx {
+        months: Vec<u32>,
+        default_months: u32,
+    }
+
+    let res =
+        s.t.render(
+            "reports.trends.get.hbs",
+            &Ctx {
+                months: vec![3, 6, 12, 18, 24],
+                default_months: api::DEFAULT_TREND_MONTHS,
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
+
+#[axum::debug_handler]
+async fn get_sankey(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let (from, to) = month_range(None, None).map_err(|err| AppMessage::new_error(err, &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        from: String,
+        to: String,
+    }
+
+    let res =
+        s.t.render(
+            "reports.sankey.get.hbs",
+            &Ctx {
+                from: from.format("%Y-%m").to_string(),
+                to: to.format("%Y-%m").to_string(),
+            },
+        )
+        .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
@@ -0,0 +1,88 @@
+use crate::{
+    front::{accounts::AppMessage, AppState},
+    recurring,
+};
+use anyhow::anyhow;
+use axum::{
+    extract::State,
+    http::HeaderValue,
+    response::{IntoResponse, Response},
+    Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get))
+        .route("/detect", axum::routing::post(detect))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    #[derive(sqlx::FromRow, Serialize)]
+    struct Record {
+        counterparty: String,
+        source_account: String,
+        currency: String,
+        period: String,
+        occurrences: i32,
+        last_date: NaiveDate,
+        next_expected_date: NaiveDate,
+        average_amount: BigDecimal,
+        last_amount: BigDecimal,
+        annual_cost: BigDecimal,
+        price_increased: bool,
+        stopped: bool,
+    }
+
+    let subscriptions = sqlx::query_as::<_, Record>(
+        r#"
+        SELECT
+            counterparty,
+            source_account,
+            currency,
+            period,
+            occurrences,
+            last_date,
+            next_expected_date,
+            average_amount,
+            last_amount,
+            annual_cost,
+            price_increased,
+            stopped
+        FROM subscription
+        ORDER BY stopped ASC, annual_cost ASC
+        "#,
+    )
+    .fetch_all(&s.p)
+    .await
+    .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+
+    #[derive(Serialize)]
+    struct Ctx {
+        subscriptions: Vec<Record>,
+    }
+
+    let ret =
+        s.t.render("api.subscriptions.get.hbs", &Ctx { subscriptions })
+            .map_err(|err| AppMessage::new_error_notification(anyhow!(err), &s))?;
+    Ok(ret)
+}
+
+#[axum::debug_handler]
+async fn detect(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let count = recurring::detect(&s.p)
+        .await
+        .map_err(|err| AppMessage::new_error_notification(err, &s))?;
+
+    let mut ret =
+        AppMessage::new_info_notification(format!("{} recurring payments detected", count), &s)
+            .into_response();
+    ret.headers_mut().insert(
+        "HX-Trigger",
+        HeaderValue::from_static("subscriptions-updated"),
+    );
+    Ok(ret)
+}
@@ -0,0 +1,17 @@
+pub mod api;
+
+use super::{accounts::AppMessage, AppState};
+use anyhow::anyhow;
+use axum::{extract::State, response::Response, Router};
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(get))
+}
+
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>) -> Result<Response, AppMessage> {
+    let res =
+        s.t.render("subscriptions.get.hbs", &())
+            .map_err(|err| AppMessage::new_error(anyhow!(err), &s))?;
+    Ok(res)
+}
@@ -1,71 +1,250 @@
 use std::{
-    fs,
+    collections::BTreeMap,
     sync::{Arc, RwLock},
 };
 
+use anyhow::Context;
 use axum::{
+    extract::Request,
     http::StatusCode,
+    middleware::Next,
     response::{Html, IntoResponse, Response},
 };
-use handlebars::{handlebars_helper, RenderError};
+use handlebars::{handlebars_helper, html_escape, RenderError, RenderErrorReason, ScopedJson};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::Serialize;
 use serde_json::Value;
 
+use crate::assets::{self, Kind};
+
 /// Condig standards:
 /// - helpers functions should be in snake_case
 /// - template variables should be in snake_case
 #[derive(Clone)]
 pub struct Template {
-    r: Arc<RwLock<handlebars::Handlebars<'static>>>,
+    r: Arc<RwLock<Registry>>,
 }
 
-impl Template {
-    pub fn new() -> Self {
-        let mut handlebars = handlebars::Handlebars::new();
-        for entitiy in fs::read_dir("./src/front/templates").unwrap() {
-            let entity = entitiy.unwrap();
-            let res = handlebars
-                .register_template_file(entity.file_name().to_str().unwrap(), entity.path());
-            if let Err(err) = res {
-                log::info!("{}", err);
-                panic!("stop");
-            }
-        }
+struct Registry {
+    handlebars: handlebars::Handlebars<'static>,
+    /// Templates which do not compile, with the error. While there are any,
+    /// every page shows them instead, partials may be missing.
+    errors: BTreeMap<String, String>,
+}
 
-        handlebars_helper!(normalizeAmount: |i: String| format!("{:.02}", i.parse::<f64>().unwrap_or(0.0)));
-        handlebars_helper!(range: |a1: Value, a2: Value | (a1.as_i64().unwrap()..=a2.as_i64().unwrap()).collect::<Vec<i64>>() );
-        handlebars_helper!(toMonthString: |m: Value| {
-            match m.as_i64().unwrap_or(0) {
-                1 => format!("January"),
-                2 => format!("February"),
-                3 => format!("March"),
-                4 => format!("April"),
-                5 => format!("May"),
-                6 => format!("June"),
-                7 => format!("July"),
-                8 => format!("August"),
-                9 => format!("September"),
-                10 => format!("October"),
-                11 => format!("November"),
-                12 => format!("December"),
-                _ => format!("?")
-            }
-        } );
+/// Compile errors of templates as HTML, set on the errors page, see `errors_fragment`.
+#[derive(Clone)]
+struct TemplateErrors(String);
 
-        handlebars.register_helper("normalizeAmount", Box::new(normalizeAmount));
-        handlebars.register_helper("toMonthString", Box::new(toMonthString));
-        handlebars.register_helper("range", Box::new(range));
+impl Template {
+    /// `locale` is available to templates as `{{locale}}`.
+    pub fn new(locale: &str) -> Self {
+        let mut registry = Registry {
+            handlebars: new_handlebars(locale),
+            errors: BTreeMap::new(),
+        };
+        registry.load();
         Self {
-            r: Arc::new(RwLock::new(handlebars)),
+            r: Arc::new(RwLock::new(registry)),
         }
     }
 
+    /// Registers all templates again, e.g. after they were edited.
+    pub fn reload(&self) {
+        self.r.write().unwrap().load();
+    }
+
+    /// Reloads templates when a file in the templates directory changes. They
+    /// are read from disk only with an override directory, see `assets`.
+    /// Watching stops when the returned watcher is dropped.
+    pub fn watch(&self) -> anyhow::Result<RecommendedWatcher> {
+        let dir = assets::dir(Kind::Templates);
+        let t = self.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                // editors also touch swap and backup files
+                Ok(event)
+                    if !event.kind.is_access()
+                        && event
+                            .paths
+                            .iter()
+                            .any(|p| p.extension().is_some_and(|e| e == "hbs")) =>
+                {
+                    log::info!("templates changed, reloading");
+                    t.reload();
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("watching templates: {}", err),
+            })?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("cannot watch {}", dir.display()))?;
+        log::info!("watching templates in {}", dir.display());
+        Ok(watcher)
+    }
+
     pub fn render<T>(&self, name: &str, data: &T) -> anyhow::Result<Response>
     where
         T: Serialize,
     {
         log::info!("render '{}': {:#?}", name, serde_json::to_value(data));
-        let html = Html(self.r.read().unwrap().render(name, data)?);
+        let r = self.r.read().unwrap();
+        if !r.errors.is_empty() {
+            return Ok(r.errors_page());
+        }
+        let html = Html(r.handlebars.render(name, data)?);
         Ok(html.into_response())
     }
 }
+
+impl Registry {
+    fn load(&mut self) {
+        self.handlebars.clear_templates();
+        self.errors.clear();
+
+        let files = match assets::list(Kind::Templates) {
+            Ok(files) => files,
+            Err(err) => {
+                log::error!("{:#}", err);
+                self.errors
+                    .insert("templates".to_string(), format!("{:#}", err));
+                return;
+            }
+        };
+        for file in files {
+            let res = file
+                .text()
+                .and_then(|text| Ok(self.handlebars.register_template_string(&file.name, text)?));
+            if let Err(err) = res {
+                log::error!("{}", err);
+                self.errors.insert(file.name, err.to_string());
+            }
+        }
+    }
+
+    /// Plain page which does not depend on any template, they may be broken.
+    fn errors_page(&self) -> Response {
+        let errors: String = self
+            .errors
+            .iter()
+            .map(|(name, err)| {
+                format!(
+                    "<h2>{}</h2><pre>{}</pre>",
+                    html_escape(name),
+                    html_escape(err)
+                )
+            })
+            .collect();
+        let mut res = (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html(format!(
+                "<!DOCTYPE html><html><head><title>Template error</title></head><body><h1>Templates do not compile</h1>{}</body></html>",
+                errors
+            )),
+        )
+            .into_response();
+        res.extensions_mut().insert(TemplateErrors(errors));
+        res
+    }
+}
+
+/// Replaces the errors page with a notification for htmx requests, otherwise
+/// a whole document would be swapped into the page.
+pub async fn errors_fragment(req: Request, next: Next) -> Response {
+    let htmx = req.headers().contains_key("HX-Request");
+    let res = next.run(req).await;
+    match res.extensions().get::<TemplateErrors>() {
+        Some(TemplateErrors(errors)) if htmx => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Html(format!(
+                r#"<div class="notification is-danger"><strong>Templates do not compile</strong>{}</div>"#,
+                errors
+            )),
+        )
+            .into_response(),
+        _ => res,
+    }
+}
+
+/// `(range 1 12)`, both bounds included. Other values than integers fail the
+/// render, templates can be edited while the server runs.
+struct Range;
+
+impl handlebars::HelperDef for Range {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &handlebars::Helper<'rc>,
+        _: &'reg handlebars::Handlebars<'reg>,
+        _: &'rc handlebars::Context,
+        _: &mut handlebars::RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let bound = |i: usize| -> Result<i64, RenderError> {
+            let param = h
+                .param(i)
+                .ok_or(RenderErrorReason::ParamNotFoundForIndex("range", i))?;
+            param.value().as_i64().ok_or_else(|| {
+                RenderErrorReason::ParamTypeMismatchForName(
+                    "range",
+                    param.relative_path().cloned().unwrap_or(i.to_string()),
+                    "integer".to_string(),
+                )
+                .into()
+            })
+        };
+        let values: Vec<i64> = (bound(0)?..=bound(1)?).collect();
+        Ok(ScopedJson::Derived(Value::from(values)))
+    }
+}
+
+fn new_handlebars(locale: &str) -> handlebars::Handlebars<'static> {
+    let mut handlebars = handlebars::Handlebars::new();
+
+    handlebars_helper!(normalizeAmount: |i: String| format!("{:.02}", i.parse::<f64>().unwrap_or(0.0)));
+    handlebars_helper!(money: |amount: Value, currency: str| {
+        let amount = match amount {
+            Value::String(s) => s.parse::<f64>().unwrap_or(0.0),
+            v => v.as_f64().unwrap_or(0.0),
+        };
+        crate::currency::format_amount(amount, currency)
+    });
+    handlebars_helper!(join: |v: Vec<String>, separator: str| v.join(separator));
+    handlebars_helper!(toMonthString: |m: Value| {
+            match m.as_i64().unwrap_or(0) {
+                1 => "January".to_string(),
+                2 => "February".to_string(),
+                3 => "March".to_string(),
+                4 => "April".to_string(),
+                5 => "May".to_string(),
+                6 => "June".to_string(),
+                7 => "July".to_string(),
+                8 => "August".to_string(),
+                9 => "September".to_string(),
+                10 => "October".to_string(),
+                11 => "November".to_string(),
+                12 => "December".to_string(),
+                _ => "?".to_string()
+            }
+        } );
+
+    handlebars.register_helper("normalizeAmount", Box::new(normalizeAmount));
+    handlebars.register_helper("toMonthString", Box::new(toMonthString));
+    handlebars.register_helper("range", Box::new(Range));
+    handlebars.register_helper("join", Box::new(join));
+    handlebars.register_helper("money", Box::new(money));
+    let locale = locale.to_string();
+    handlebars.register_helper(
+        "locale",
+        Box::new(
+            move |_: &handlebars::Helper,
+                  _: &handlebars::Handlebars,
+                  _: &handlebars::Context,
+                  _: &mut handlebars::RenderContext,
+                  out: &mut dyn handlebars::Output|
+                  -> handlebars::HelperResult {
+                out.write(&locale)?;
+                Ok(())
+            },
+        ),
+    );
+    handlebars
+}
@@ -0,0 +1,67 @@
+use super::{ApiError, ErrorBody, Page};
+use crate::front::{components::table, AppState};
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(list))
+}
+
+#[derive(sqlx::FromRow, Serialize, Default, ToSchema)]
+pub struct Account {
+    id: Uuid,
+    name: String,
+    /// Account numbers, entries belong to the account by their `source_account`.
+    numbers: Vec<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/accounts",
+    params(table::Query),
+    responses(
+        (status = 200, body = Page<Account>),
+        (status = 400, description = "Invalid sort column", body = ErrorBody)
+    )
+)]
+#[axum::debug_handler]
+async fn list(
+    State(s): State<AppState>,
+    Query(t): Query<table::Query>,
+) -> Result<Response, ApiError> {
+    let t = t.noramlize::<Account>()?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM account")
+        .fetch_one(&s.p)
+        .await?;
+
+    let accounts = sqlx::query_as::<_, Account>(&format!(
+        r#"
+        SELECT
+            a.id,
+            a.name,
+            ARRAY(
+                SELECT r.reference FROM account_reference r
+                WHERE r.account_id = a.id
+                ORDER BY r.reference
+            ) AS numbers
+        FROM account a
+        ORDER BY {}
+        LIMIT $1
+        OFFSET $2
+        "#,
+        t.order_by("name ASC, id ASC")
+    ))
+    .bind(t.limit())
+    .bind(t.offset())
+    .fetch_all(&s.p)
+    .await?;
+
+    Ok(Json(Page::new(accounts, total, &t)).into_response())
+}
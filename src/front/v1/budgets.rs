@@ -0,0 +1,29 @@
+use super::ErrorBody;
+use crate::front::AppState;
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(list))
+}
+
+/// The app does not track budgets yet. The path is reserved so clients get a
+/// clear answer instead of a missing route.
+#[utoipa::path(
+    get,
+    path = "/api/v1/budgets",
+    responses((status = 501, description = "Budgets are not available yet", body = ErrorBody))
+)]
+#[axum::debug_handler]
+async fn list() -> Response {
+    (
+        StatusCode::NOT_IMPLEMENTED,
+        Json(ErrorBody {
+            error: "budgets are not available yet".to_string(),
+        }),
+    )
+        .into_response()
+}
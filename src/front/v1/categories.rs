@@ -0,0 +1,42 @@
+use super::{ApiError, List};
+use crate::front::AppState;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/", axum::routing::get(list))
+}
+
+#[derive(sqlx::FromRow, Serialize, ToSchema)]
+pub struct Category {
+    name: String,
+    /// Number of entries in the category.
+    entries: i64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/categories",
+    responses((status = 200, body = List<Category>))
+)]
+#[axum::debug_handler]
+async fn list(State(s): State<AppState>) -> Result<Response, ApiError> {
+    let categories = sqlx::query_as::<_, Category>(
+        r#"
+        SELECT category AS name, COUNT(*) AS entries
+        FROM entry
+        WHERE category <> ''
+        GROUP BY category
+        ORDER BY category ASC
+        "#,
+    )
+    .fetch_all(&s.p)
+    .await?;
+
+    Ok(Json(List { data: categories }).into_response())
+}
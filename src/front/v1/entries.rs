@@ -0,0 +1,120 @@
+use super::{ApiError, ErrorBody, Page};
+use crate::{
+    error::{BadRequest, NotFound},
+    front::{components::table, filtered_entries, AppState, EntryQuery},
+};
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(list))
+        .route("/:id", axum::routing::get(get))
+}
+
+const COLUMNS: &str = r#"
+    e.id,
+    e.accounting_date,
+    e.currency_date,
+    e.sender_or_receiver,
+    e.address,
+    e.source_account,
+    e.destination_account,
+    e.title,
+    e.amount,
+    e.currency,
+    e.reference_number,
+    e.operation_type,
+    e.category,
+    e.note,
+    e.tags,
+    e.payee_id,
+    e.excluded_from_reports,
+    e.is_transfer,
+    e.manual
+"#;
+
+#[derive(sqlx::FromRow, Serialize, Default, ToSchema)]
+pub struct Entry {
+    id: Uuid,
+    accounting_date: NaiveDate,
+    currency_date: NaiveDate,
+    sender_or_receiver: String,
+    address: String,
+    source_account: String,
+    destination_account: String,
+    title: String,
+    /// Negative for expenses, in `currency`.
+    #[schema(value_type = String, example = "-12.50")]
+    amount: BigDecimal,
+    currency: String,
+    reference_number: String,
+    operation_type: String,
+    category: String,
+    note: String,
+    tags: Vec<String>,
+    payee_id: Option<Uuid>,
+    excluded_from_reports: bool,
+    is_transfer: bool,
+    /// Added by the user, not imported from a bank file.
+    manual: bool,
+}
+
+/// Filters are the same as of the transactions list, e.g. `?q=shop&from=2024-01-01&sort=amount`.
+#[utoipa::path(
+    get,
+    path = "/api/v1/entries",
+    params(table::Query, EntryQuery),
+    responses(
+        (status = 200, body = Page<Entry>),
+        (status = 400, description = "Invalid filter or sort column", body = ErrorBody)
+    )
+)]
+#[axum::debug_handler]
+async fn list(
+    State(s): State<AppState>,
+    Query(t): Query<table::Query>,
+    Query(query): Query<EntryQuery>,
+) -> Result<Response, ApiError> {
+    let t = t.noramlize::<Entry>()?;
+    let f = query.normalize().filter()?;
+
+    let (total, entries) = filtered_entries::<Entry>(&s.p, &f, COLUMNS, &t).await?;
+
+    Ok(Json(Page::new(entries, total, &t)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/entries/{id}",
+    params(("id" = Uuid, Path)),
+    responses(
+        (status = 200, body = Entry),
+        (status = 400, description = "Invalid id", body = ErrorBody),
+        (status = 404, body = ErrorBody)
+    )
+)]
+#[axum::debug_handler]
+async fn get(State(s): State<AppState>, Path(id): Path<String>) -> Result<Response, ApiError> {
+    let id = id
+        .parse::<Uuid>()
+        .map_err(|err| anyhow!(BadRequest(format!("invalid entry id: {}", err))))?;
+
+    let entry =
+        sqlx::query_as::<_, Entry>(&format!("SELECT {} FROM entry e WHERE e.id = $1", COLUMNS))
+            .bind(id)
+            .fetch_optional(&s.p)
+            .await?
+            .ok_or(anyhow!(NotFound(format!("entry {} does not exist", id))))?;
+
+    Ok(Json(entry).into_response())
+}
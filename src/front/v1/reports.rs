@@ -0,0 +1,70 @@
+use super::ApiError;
+use crate::{
+    currency,
+    front::{expenses_of_main_account, AppState, ExpensesQuery, GroupBy, GroupedAmount},
+};
+use axum::{
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    Json, Router,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+pub fn new_router() -> Router<AppState> {
+    Router::new().route("/expenses", axum::routing::get(expenses))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Expenses {
+    from: NaiveDate,
+    /// Exclusive.
+    to: NaiveDate,
+    /// `category` or `merchant`
+    group_by: &'static str,
+    base_currency: String,
+    /// Biggest expense first, amounts are negative and in the base currency.
+    data: Vec<GroupedAmount>,
+    /// Entries left out of `data`, of any period, because they have no exchange
+    /// rate to the base currency.
+    unconverted: Vec<currency::Unconverted>,
+}
+
+/// Expenses of the main account, parameters are the same as of the expenses page
+/// (`year`, `month`, `period`, `group_by`, `max_elements`).
+#[utoipa::path(
+    get,
+    path = "/api/v1/reports/expenses",
+    params(ExpensesQuery),
+    responses((status = 200, body = Expenses))
+)]
+#[axum::debug_handler]
+async fn expenses(
+    State(s): State<AppState>,
+    Query(q): Query<ExpensesQuery>,
+) -> Result<Response, ApiError> {
+    let (from, to) = q.period();
+    let group_by = q.group_by();
+
+    let mut data = expenses_of_main_account(&s.p, group_by, from, to).await?;
+    if let Some(m) = q.max_elements {
+        data.truncate(m as usize);
+    }
+
+    let base_currency = currency::base_currency(&s.p).await?;
+    let unconverted = currency::unconverted(&s.p).await?;
+
+    Ok(Json(Expenses {
+        from,
+        to,
+        group_by: match group_by {
+            GroupBy::Merchant => "merchant",
+            _ => "category",
+        },
+        base_currency,
+        data,
+        unconverted,
+    })
+    .into_response())
+}
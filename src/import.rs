@@ -0,0 +1,191 @@
+use std::io;
+
+use anyhow::{anyhow, bail, Context};
+use csv::StringRecord;
+use sqlx::{Pool, Postgres};
+
+use crate::error::BadRequest;
+
+/// Bank statement exports which can be imported.
+#[derive(Clone, Copy, Default, clap::ValueEnum)]
+pub enum Format {
+    /// `;` separated, `DD.MM.YYYY` dates, amounts with a decimal comma
+    #[default]
+    Mbank,
+}
+
+/// Imports entries of a statement and returns the number of new ones. Entries which
+/// were imported before or merged into another one are skipped. With `account`
+/// (a name or a number) the whole file is rejected when it has entries of other accounts.
+pub async fn load(
+    p: &Pool<Postgres>,
+    format: Format,
+    s: impl io::Read,
+    account: Option<&str>,
+) -> anyhow::Result<u64> {
+    let records = match format {
+        Format::Mbank => read_mbank(s)?,
+    };
+    if let Some(account) = account {
+        check_account(p, account, &records).await?;
+    }
+    insert(p, &records).await
+}
+
+/// Links new entries to payees and detects recurring payments, failures are only logged.
+pub async fn after_load(p: &Pool<Postgres>) {
+    if let Err(err) = crate::payees::link_entries(p).await {
+        log::error!("cannot link entries to payees: {}", err);
+    }
+
+    if let Err(err) = crate::recurring::detect(p).await {
+        log::error!("cannot detect recurring payments: {}", err);
+    }
+}
+
+fn read_mbank(s: impl io::Read) -> anyhow::Result<Vec<StringRecord>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .delimiter(b';')
+        .from_reader(s);
+    let records = rdr
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BadRequest(format!("invalid CSV file: {}", err)))?;
+    if let Some(r) = records.iter().find(|r| r.len() < 12) {
+        bail!(BadRequest(format!(
+            "line {}: expected 12 columns, got {}",
+            r.position().map(|p| p.line()).unwrap_or_default(),
+            r.len()
+        )));
+    }
+    Ok(records)
+}
+
+/// The source account (column 5) of every entry has to be a number of `account`.
+async fn check_account(
+    p: &Pool<Postgres>,
+    account: &str,
+    records: &[StringRecord],
+) -> anyhow::Result<()> {
+    let references: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT r.reference
+        FROM account a
+        JOIN account_reference r ON r.account_id = a.id
+        WHERE a.id IN (
+            SELECT a.id FROM account a WHERE LOWER(a.name) = LOWER($1)
+            UNION
+            SELECT r.account_id FROM account_reference r WHERE r.reference = REPLACE($1, ' ', '')
+        )
+        "#,
+    )
+    .bind(account)
+    .fetch_all(p)
+    .await?;
+    if references.is_empty() {
+        bail!(BadRequest(format!("account '{}' does not exist", account)));
+    }
+
+    let other = records
+        .iter()
+        .map(|r| r[4].replace(' ', ""))
+        .find(|a| !references.contains(a));
+    if let Some(other) = other {
+        bail!(BadRequest(format!(
+            "the file has entries of account {}, which is not '{}'",
+            other, account
+        )));
+    }
+    Ok(())
+}
+
+// todo: this need to be refactored because data was inseeted incorectly, migrate db again
+async fn insert(p: &Pool<Postgres>, records: &[StringRecord]) -> anyhow::Result<u64> {
+    let mut count = 0;
+
+    for record in records {
+        let insert_query = r#"INSERT INTO entry (
+                 accounting_date,
+                 currency_date,
+                 sender_or_receiver,
+                 address,
+                 source_account,
+                 destination_account,
+                 title,
+                 amount,
+                 currency,
+                 reference_number,
+                 operation_type,
+                 category,
+                 original_title,
+                 original_category
+            ) SELECT
+                to_date($1, 'DD.MM.YYYY'),
+                to_date($2, 'DD.MM.YYYY'),
+                $3,
+                $4,
+                $5,
+                $6,
+                $7,
+                $8,
+                $9,
+                $10,
+                $11,
+                $12,
+                $7,
+                $12
+            -- merged duplicates are not imported again
+            WHERE NOT EXISTS (
+                SELECT 1 FROM merged_reference WHERE reference_number = $10
+            );"#;
+
+        let amount = record[7]
+            .replace(",", ".")
+            .replace(" ", "")
+            .parse::<f64>()
+            .with_context(|| BadRequest(format!("value: {}", &record[7])))?;
+
+        match sqlx::query(insert_query)
+            .bind(&record[0])
+            .bind(&record[1])
+            .bind(&record[2])
+            .bind(&record[3])
+            .bind(&record[4])
+            .bind(&record[5])
+            .bind(&record[6])
+            .bind(amount)
+            .bind(&record[8])
+            .bind(&record[9])
+            .bind(&record[10])
+            .bind(&record[11])
+            .execute(p)
+            .await
+        {
+            Ok(res) => {
+                count += res.rows_affected();
+            }
+            Err(err) => {
+                if let Some(err) = err.as_database_error() {
+                    // already imported, the entry and edits made by the user are kept
+                    if err.is_unique_violation() {
+                        log::warn!(
+                            "cannnt load entry: violation for entry {:?}: {}",
+                            record,
+                            err
+                        );
+                        continue;
+                    }
+                }
+
+                log::error!("cannot insert: {}: {}", insert_query, err);
+
+                return Err(anyhow!(err));
+            }
+        };
+    }
+
+    log::info!("{} records were loaded to db", count);
+
+    Ok(count)
+}
@@ -1,36 +1,535 @@
-use core::panic;
-use std::{any::Any, borrow::BorrowMut, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    time::Instant,
+};
 
-use sqlx::{Execute, Pool, Postgres};
-pub async fn migrate(p: &Pool<Postgres>) {
-    let mut migration_files: Vec<(usize, PathBuf)> = Vec::new();
-    for entry in fs::read_dir("./migrations").unwrap() {
-        let entry = entry.unwrap();
+use crate::assets::{self, File, Kind};
 
-        if !entry.metadata().unwrap().is_file() {
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::{pool::PoolConnection, PgConnection, Pool, Postgres};
+
+/// Applied migrations. Databases created before this table existed get every
+/// migration applied once more, which is safe because all of them use
+/// `IF NOT EXISTS` or `OR REPLACE`.
+const CREATE_SCHEMA_MIGRATIONS: &str = r#"
+    CREATE TABLE IF NOT EXISTS schema_migrations (
+        version             BIGINT NOT NULL PRIMARY KEY,
+        name                TEXT NOT NULL,
+        checksum            TEXT NOT NULL,
+        applied_at          TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+        duration_ms         BIGINT NOT NULL
+    )
+"#;
+
+/// Any number which is not used by other advisory locks, it keeps two instances
+/// started at the same time from applying the same migration.
+const MIGRATION_LOCK_ID: i64 = 0x6275_6467_6574;
+
+/// `NNN_name.up.sql` with an optional `NNN_name.down.sql` which reverts it.
+/// Files without `.up` are up migrations too.
+#[derive(Debug)]
+struct Migration {
+    version: i64,
+    name: String,
+    location: String,
+    sql: String,
+    /// Only the up file is checked for changes, down files can be fixed later.
+    checksum: String,
+    /// SQL and location of the down file.
+    down: Option<(String, String)>,
+}
+
+#[derive(sqlx::FromRow)]
+struct Applied {
+    version: i64,
+    name: String,
+    checksum: String,
+    applied_at: DateTime<Utc>,
+}
+
+/// State of one migration, known from files or from `schema_migrations`.
+pub struct Status {
+    pub version: i64,
+    pub name: String,
+    pub applied_at: Option<DateTime<Utc>>,
+    /// The up file differs from the applied one.
+    pub changed: bool,
+    /// Applied, but there is no up file.
+    pub missing: bool,
+    pub reversible: bool,
+}
+
+/// A migration which `down` would revert.
+pub struct Revert {
+    pub location: String,
+    /// Statements which drop tables or columns, their data is lost.
+    pub drops: Vec<String>,
+}
+
+fn checksum(sql: &str) -> String {
+    format!("{:x}", Sha256::digest(sql.as_bytes()))
+}
+
+fn read_migrations() -> anyhow::Result<Vec<Migration>> {
+    parse_migrations(assets::list(Kind::Migrations).context("cannot read migrations")?)
+}
+
+/// Pairs up and down files by version, ordered by version.
+fn parse_migrations(list: Vec<File>) -> anyhow::Result<Vec<Migration>> {
+    #[derive(Default)]
+    struct Files {
+        name: String,
+        up: Option<File>,
+        down: Option<File>,
+    }
+
+    let mut files: BTreeMap<i64, Files> = BTreeMap::new();
+    for file in list {
+        let Some(stem) = file.name.strip_suffix(".sql") else {
             continue;
+        };
+        let (stem, is_down) = match stem.strip_suffix(".down") {
+            Some(stem) => (stem, true),
+            None => (stem.strip_suffix(".up").unwrap_or(stem), false),
+        };
+        let (version, name) = stem
+            .split_once('_')
+            .and_then(|(v, n)| Some((v.parse::<i64>().ok()?, n.to_string())))
+            .ok_or(anyhow!(
+                "migration file name should look like 001_create_table.up.sql: {}",
+                file.location
+            ))?;
+
+        let f = files.entry(version).or_default();
+        if !f.name.is_empty() && f.name != name {
+            bail!(
+                "migrations {:03}_{} and {} have the same version",
+                version,
+                f.name,
+                file.location
+            );
         }
+        f.name = name;
+        let location = file.location.clone();
+        let slot = if is_down { &mut f.down } else { &mut f.up };
+        if let Some(other) = slot.replace(file) {
+            bail!(
+                "migrations {} and {} have the same version",
+                other.location,
+                location
+            );
+        }
+    }
 
-        if entry.path().extension().unwrap() != "sql" {
-            continue;
+    files
+        .into_iter()
+        .map(|(version, f)| {
+            let up = f.up.ok_or(anyhow!(
+                "migration {:03}_{} has a down file but no up file",
+                version,
+                f.name
+            ))?;
+            let sql = up.text()?.to_string();
+            let down = match f.down {
+                Some(down) => Some((down.text()?.to_string(), down.location)),
+                None => None,
+            };
+            Ok(Migration {
+                version,
+                name: f.name,
+                checksum: checksum(&sql),
+                location: up.location,
+                sql,
+                down,
+            })
+        })
+        .collect()
+}
+
+/// Takes the migration lock on a dedicated connection, it is released by `unlock`
+/// or when the connection is closed.
+async fn lock(p: &Pool<Postgres>) -> anyhow::Result<PoolConnection<Postgres>> {
+    let mut conn = p.acquire().await?;
+    sqlx::query("SELECT pg_advisory_lock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::raw_sql(CREATE_SCHEMA_MIGRATIONS)
+        .execute(&mut *conn)
+        .await
+        .context("cannot create schema_migrations table")?;
+    Ok(conn)
+}
+
+async fn unlock(conn: &mut PgConnection) -> anyhow::Result<()> {
+    sqlx::query("SELECT pg_advisory_unlock($1)")
+        .bind(MIGRATION_LOCK_ID)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+async fn applied(conn: &mut PgConnection) -> anyhow::Result<HashMap<i64, Applied>> {
+    Ok(sqlx::query_as::<_, Applied>(
+        "SELECT version, name, checksum, applied_at FROM schema_migrations",
+    )
+    .fetch_all(conn)
+    .await?
+    .into_iter()
+    .map(|a| (a.version, a))
+    .collect())
+}
+
+/// Fails when an applied migration was changed or removed.
+fn check_drift(migrations: &[Migration], applied: &HashMap<i64, Applied>) -> anyhow::Result<()> {
+    for a in applied.values() {
+        match migrations.iter().find(|m| m.version == a.version) {
+            None => bail!(
+                "migration {:03}_{} was applied but its file is missing",
+                a.version,
+                a.name
+            ),
+            Some(m) if m.checksum != a.checksum => bail!(
+                "migration {} was changed after it was applied, add a new migration instead",
+                m.location
+            ),
+            _ => {}
         }
+    }
+    Ok(())
+}
+
+/// The last `n` applied migrations, newest first.
+fn last_applied<'a>(
+    migrations: &'a [Migration],
+    applied: &HashMap<i64, Applied>,
+    n: usize,
+) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .rev()
+        .filter(|m| applied.contains_key(&m.version))
+        .take(n)
+        .collect()
+}
+
+/// Statements of `sql` which drop a table or a column.
+fn drops(sql: &str) -> Vec<String> {
+    sql.split(';')
+        .map(|s| {
+            s.lines()
+                .filter(|l| !l.trim_start().starts_with("--"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .map(|s| s.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|s| {
+            let s = s.to_uppercase();
+            s.contains("DROP TABLE") || s.contains("DROP COLUMN")
+        })
+        .collect()
+}
 
-        let path = &entry.path();
-        let (migration_number, _) = path.file_name().unwrap().to_str().unwrap().split_at(3);
-        let migration_number: usize = migration_number.parse().unwrap();
+/// Applies migrations which are not in `schema_migrations` yet, in order of
+/// their version, each one in its own transaction. Fails without applying
+/// anything when an already applied migration was changed or removed.
+/// Returns the number of applied migrations.
+pub async fn up(p: &Pool<Postgres>) -> anyhow::Result<usize> {
+    let migrations = read_migrations()?;
+    let mut conn = lock(p).await?;
+    let res = apply(&mut conn, &migrations).await;
+    unlock(&mut conn).await?;
+    res
+}
+
+async fn apply(conn: &mut PgConnection, migrations: &[Migration]) -> anyhow::Result<usize> {
+    let applied = applied(conn).await?;
+    check_drift(migrations, &applied)?;
 
-        migration_files.push((migration_number, entry.path()))
+    let pending: Vec<&Migration> = migrations
+        .iter()
+        .filter(|m| !applied.contains_key(&m.version))
+        .collect();
+    if pending.is_empty() {
+        log::info!("database schema is up to date");
+        return Ok(0);
     }
 
-    migration_files.sort_by_key(|v| v.0);
+    log::info!("applying {} migrations", pending.len());
+    for m in &pending {
+        log::info!("migrating {}", m.location);
+        let started = Instant::now();
 
-    log::info!("starting migrationg");
-    for (_, dir) in &migration_files {
-        let content = fs::read_to_string(dir).unwrap();
-        let path = dir.to_str().unwrap();
-        log::info!("migrating {}", path);
-        let res = sqlx::query(&content).execute(p).await.unwrap();
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::raw_sql(&m.sql)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("cannot apply migration {}", m.location))?;
+        sqlx::query(
+            r#"
+            INSERT INTO schema_migrations (version, name, checksum, duration_ms)
+            VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(m.version)
+        .bind(&m.name)
+        .bind(&m.checksum)
+        .bind(started.elapsed().as_millis() as i64)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
     }
 
     log::info!("migration end");
+    Ok(pending.len())
+}
+
+/// Migrations which `down` would revert, with the data they would drop. Fails
+/// like `down` when one of them has no down file.
+pub async fn down_plan(p: &Pool<Postgres>, n: usize) -> anyhow::Result<Vec<Revert>> {
+    let migrations = read_migrations()?;
+    let mut conn = lock(p).await?;
+    let applied = applied(&mut conn).await;
+    unlock(&mut conn).await?;
+    let applied = applied?;
+    check_drift(&migrations, &applied)?;
+
+    last_applied(&migrations, &applied, n)
+        .into_iter()
+        .map(|m| match &m.down {
+            Some((sql, location)) => Ok(Revert {
+                location: location.clone(),
+                drops: drops(sql),
+            }),
+            None => Err(anyhow!(
+                "migration {} cannot be reverted, it has no down file",
+                m.location
+            )),
+        })
+        .collect()
+}
+
+/// Reverts the last `n` applied migrations, newest first, each one in its own
+/// transaction. Fails before reverting anything when one of them has no down file.
+/// Returns the number of reverted migrations.
+pub async fn down(p: &Pool<Postgres>, n: usize) -> anyhow::Result<usize> {
+    let migrations = read_migrations()?;
+    let mut conn = lock(p).await?;
+    let res = revert(&mut conn, &migrations, n).await;
+    unlock(&mut conn).await?;
+    res
+}
+
+async fn revert(
+    conn: &mut PgConnection,
+    migrations: &[Migration],
+    n: usize,
+) -> anyhow::Result<usize> {
+    let applied = applied(conn).await?;
+    check_drift(migrations, &applied)?;
+
+    let last = last_applied(migrations, &applied, n);
+    if let Some(m) = last.iter().find(|m| m.down.is_none()) {
+        bail!(
+            "migration {} cannot be reverted, it has no down file",
+            m.location
+        );
+    }
+
+    for m in &last {
+        let Some((sql, location)) = &m.down else {
+            continue;
+        };
+        log::info!("reverting {}", location);
+
+        let mut tx = sqlx::Connection::begin(&mut *conn).await?;
+        sqlx::raw_sql(sql)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("cannot revert migration {}", location))?;
+        sqlx::query("DELETE FROM schema_migrations WHERE version = $1")
+            .bind(m.version)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+    }
+
+    Ok(last.len())
+}
+
+/// Every migration from files and from `schema_migrations`, ordered by version.
+pub async fn status(p: &Pool<Postgres>) -> anyhow::Result<Vec<Status>> {
+    let migrations = read_migrations()?;
+    let mut conn = lock(p).await?;
+    let applied = applied(&mut conn).await;
+    unlock(&mut conn).await?;
+    let mut applied = applied?;
+
+    let mut statuses: Vec<Status> = migrations
+        .iter()
+        .map(|m| {
+            let a = applied.remove(&m.version);
+            Status {
+                version: m.version,
+                name: m.name.clone(),
+                changed: a.as_ref().is_some_and(|a| a.checksum != m.checksum),
+                applied_at: a.map(|a| a.applied_at),
+                missing: false,
+                reversible: m.down.is_some(),
+            }
+        })
+        .collect();
+    statuses.extend(applied.into_values().map(|a| Status {
+        version: a.version,
+        name: a.name,
+        applied_at: Some(a.applied_at),
+        changed: false,
+        missing: true,
+        reversible: false,
+    }));
+    statuses.sort_by_key(|s| s.version);
+    Ok(statuses)
+}
+
+/// Creates empty up and down files for a migration with the next version, in the
+/// override directory when it is set, otherwise in the source tree.
+pub fn new(name: &str) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let name = name
+        .trim()
+        .to_lowercase()
+        .replace(|c: char| !c.is_ascii_alphanumeric(), "_");
+    if name.trim_matches('_').is_empty() {
+        bail!("migration name cannot be empty");
+    }
+
+    let version = read_migrations()?
+        .last()
+        .map(|m| m.version + 1)
+        .unwrap_or(0);
+    let dir = assets::dir(Kind::Migrations);
+    let up = dir.join(format!("{:03}_{}.up.sql", version, name));
+    let down = dir.join(format!("{:03}_{}.down.sql", version, name));
+    fs::write(&up, "").with_context(|| format!("cannot create {}", up.display()))?;
+    fs::write(&down, "").with_context(|| format!("cannot create {}", down.display()))?;
+    Ok((up, down))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+
+    fn file(name: &str, sql: &'static str) -> File {
+        File {
+            name: name.to_string(),
+            location: format!("migrations/{}", name),
+            contents: Cow::Borrowed(sql.as_bytes()),
+        }
+    }
+
+    #[test]
+    fn pairs_up_and_down_files_by_version() {
+        let migrations = parse_migrations(vec![
+            file(
+                "010_add_note.down.sql",
+                "ALTER TABLE entry DROP COLUMN note;",
+            ),
+            file(
+                "002_add_tags.sql",
+                "ALTER TABLE entry ADD COLUMN tags TEXT[];",
+            ),
+            file(
+                "010_add_note.up.sql",
+                "ALTER TABLE entry ADD COLUMN note TEXT;",
+            ),
+            file("README.md", "not a migration"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            migrations
+                .iter()
+                .map(|m| (m.version, m.name.as_str(), m.location.as_str()))
+                .collect::<Vec<_>>(),
+            [
+                (2, "add_tags", "migrations/002_add_tags.sql"),
+                (10, "add_note", "migrations/010_add_note.up.sql"),
+            ]
+        );
+        assert!(migrations[0].down.is_none());
+        assert_eq!(
+            migrations[1].down,
+            Some((
+                "ALTER TABLE entry DROP COLUMN note;".to_string(),
+                "migrations/010_add_note.down.sql".to_string()
+            ))
+        );
+        assert_eq!(
+            migrations[1].checksum,
+            checksum("ALTER TABLE entry ADD COLUMN note TEXT;")
+        );
+    }
+
+    #[test]
+    fn keeps_underscores_in_names() {
+        let migrations = parse_migrations(vec![file("001_create_entry_table.up.sql", "")]).unwrap();
+
+        assert_eq!(migrations[0].name, "create_entry_table");
+    }
+
+    #[test]
+    fn rejects_file_names_without_version() {
+        for name in [
+            "create_table.up.sql",
+            "v1_create_table.up.sql",
+            "001.up.sql",
+        ] {
+            let err = parse_migrations(vec![file(name, "")]).unwrap_err();
+
+            assert!(
+                err.to_string().contains("should look like"),
+                "{}: {}",
+                name,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_versions() {
+        let err = parse_migrations(vec![
+            file("003_add_tags.up.sql", ""),
+            file("003_add_notes.up.sql", ""),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "migrations 003_add_tags and migrations/003_add_notes.up.sql have the same version"
+        );
+
+        let err = parse_migrations(vec![
+            file("003_add_tags.sql", ""),
+            file("003_add_tags.up.sql", ""),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "migrations migrations/003_add_tags.sql and migrations/003_add_tags.up.sql have the same version"
+        );
+    }
+
+    #[test]
+    fn rejects_down_file_without_up_file() {
+        let err = parse_migrations(vec![file("004_add_rates.down.sql", "")]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "migration 004_add_rates has a down file but no up file"
+        );
+    }
 }
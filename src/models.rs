@@ -16,4 +16,15 @@ pub struct Entry {
     reference_number: String,
     operation_type: String,
     category: String,
+    note: String,
+    tags: Vec<String>,
+    excluded_from_reports: bool,
+    /// Title from the bank, set when the entry was imported or first edited.
+    original_title: Option<String>,
+    /// Category from the bank, set when the entry was imported or first edited.
+    original_category: Option<String>,
+    /// Added by the user, not imported from a bank file.
+    manual: bool,
+    /// Money moved between own accounts, not counted in reports.
+    is_transfer: bool,
 }
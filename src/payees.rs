@@ -0,0 +1,55 @@
+use sqlx::{Pool, Postgres};
+
+/// `sender_or_receiver` or `title` is equal to the pattern, case insensitive.
+pub const KIND_ALIAS: &str = "alias";
+/// `sender_or_receiver` or `title` contains the pattern, case insensitive, `%` matches anything.
+pub const KIND_PATTERN: &str = "pattern";
+
+pub fn is_valid_kind(kind: &str) -> bool {
+    kind == KIND_ALIAS || kind == KIND_PATTERN
+}
+
+/// Links every entry to the payee whose alias or pattern matches it. Aliases win
+/// over patterns and longer patterns over shorter ones. Entries which no longer
+/// match anything are unlinked.
+pub async fn link_entries(p: &Pool<Postgres>) -> anyhow::Result<u64> {
+    let res = sqlx::query(
+        r#"
+        WITH matched AS (
+            SELECT
+                e.id,
+                (
+                    SELECT pp.payee_id
+                    FROM payee_pattern pp
+                    WHERE
+                        (
+                            pp.kind = 'alias' AND (
+                                LOWER(TRIM(e.sender_or_receiver)) = LOWER(TRIM(pp.pattern)) OR
+                                LOWER(TRIM(e.title)) = LOWER(TRIM(pp.pattern))
+                            )
+                        ) OR (
+                            pp.kind = 'pattern' AND (
+                                e.sender_or_receiver ILIKE '%' || pp.pattern || '%' OR
+                                e.title ILIKE '%' || pp.pattern || '%'
+                            )
+                        )
+                    ORDER BY pp.kind = 'alias' DESC, LENGTH(pp.pattern) DESC
+                    LIMIT 1
+                ) AS payee_id
+            FROM entry e
+        )
+        UPDATE entry e
+        SET payee_id = matched.payee_id
+        FROM matched
+        WHERE
+            e.id = matched.id AND
+            e.payee_id IS DISTINCT FROM matched.payee_id
+        "#,
+    )
+    .execute(p)
+    .await?;
+
+    log::info!("{} entries linked to payees", res.rows_affected());
+
+    Ok(res.rows_affected())
+}
@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+
+use bigdecimal::{BigDecimal, Zero};
+use chrono::{Months, NaiveDate, TimeDelta};
+use sqlx::{Pool, Postgres};
+
+use crate::error::BadRequest;
+
+/// How many payments are needed before a weekly or monthly series is treated as recurring.
+const MIN_OCCURRENCES: usize = 3;
+/// Yearly payments are rare, two of them are already a pattern.
+const MIN_YEARLY_OCCURRENCES: usize = 2;
+/// Payments whose amount differs from the median by more than this percent are not part of the series.
+const AMOUNT_TOLERANCE_PERCENT: i64 = 30;
+/// Minimal share (in percent) of intervals that have to match the detected period.
+const REGULARITY_PERCENT: usize = 75;
+/// Last payment has to be higher than a year ago by more than this percent to be reported.
+const PRICE_INCREASE_PERCENT: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    fn from_interval(days: i64) -> Option<Period> {
+        match days {
+            5..=9 => Some(Period::Weekly),
+            26..=35 => Some(Period::Monthly),
+            350..=380 => Some(Period::Yearly),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Period::Weekly => "weekly",
+            Period::Monthly => "monthly",
+            Period::Yearly => "yearly",
+        }
+    }
+
+    pub fn per_year(&self) -> i64 {
+        match self {
+            Period::Weekly => 52,
+            Period::Monthly => 12,
+            Period::Yearly => 1,
+        }
+    }
+
+    /// Days after the expected date when a missing payment is considered stopped.
+    fn grace_days(&self) -> i64 {
+        match self {
+            Period::Weekly => 4,
+            Period::Monthly => 10,
+            Period::Yearly => 31,
+        }
+    }
+
+    pub fn next(&self, date: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            Period::Weekly => date.checked_add_signed(TimeDelta::days(7)),
+            Period::Monthly => date.checked_add_months(Months::new(1)),
+            Period::Yearly => date.checked_add_months(Months::new(12)),
+        }
+    }
+}
+
+impl TryFrom<&str> for Period {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "weekly" => Ok(Period::Weekly),
+            "monthly" => Ok(Period::Monthly),
+            "yearly" => Ok(Period::Yearly),
+            _ => Err(anyhow::anyhow!(BadRequest(format!(
+                "unknown period '{}'",
+                value
+            )))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Payment {
+    pub date: NaiveDate,
+    pub amount: BigDecimal,
+}
+
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    pub source_account: String,
+    pub counterparty: String,
+    pub currency: String,
+    pub period: Period,
+    pub occurrences: usize,
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    pub next_expected_date: NaiveDate,
+    pub average_amount: BigDecimal,
+    pub last_amount: BigDecimal,
+    pub annual_cost: BigDecimal,
+    pub price_increased: bool,
+    pub stopped: bool,
+}
+
+/// Finds a recurring series in payments made to a single counterparty.
+/// Payments have to be sorted by date.
+fn find_recurring(payments: &[Payment], today: NaiveDate) -> Option<(Period, Vec<&Payment>)> {
+    let mut amounts: Vec<BigDecimal> = payments.iter().map(|p| p.amount.abs()).collect();
+    amounts.sort();
+    let median = amounts.get(amounts.len() / 2)?.clone();
+    if median.is_zero() {
+        return None;
+    }
+
+    let tolerance = &median * BigDecimal::from(AMOUNT_TOLERANCE_PERCENT) / BigDecimal::from(100);
+    let series: Vec<&Payment> = payments
+        .iter()
+        .filter(|p| (p.amount.abs() - &median).abs() <= tolerance)
+        .filter(|p| p.date <= today)
+        .collect();
+
+    if series.len() < MIN_YEARLY_OCCURRENCES {
+        return None;
+    }
+
+    let mut intervals: Vec<i64> = series
+        .windows(2)
+        .map(|w| (w[1].date - w[0].date).num_days())
+        .collect();
+    intervals.sort();
+    let period = Period::from_interval(intervals[intervals.len() / 2])?;
+
+    let min_occurrences = match period {
+        Period::Yearly => MIN_YEARLY_OCCURRENCES,
+        _ => MIN_OCCURRENCES,
+    };
+    if series.len() < min_occurrences {
+        return None;
+    }
+
+    let regular = intervals
+        .iter()
+        .filter(|i| Period::from_interval(**i) == Some(period))
+        .count();
+    if regular * 100 < intervals.len() * REGULARITY_PERCENT {
+        return None;
+    }
+
+    Some((period, series))
+}
+
+fn summarize(
+    source_account: &str,
+    counterparty: &str,
+    currency: &str,
+    payments: &[Payment],
+    today: NaiveDate,
+) -> Option<Subscription> {
+    let (period, series) = find_recurring(payments, today)?;
+
+    let first = series.first()?;
+    let last = series.last()?;
+    // the oldest payment from the last year (with a margin for late yearly payments),
+    // so increases from a few months ago are still reported
+    let year_ago = last.date.checked_sub_months(Months::new(13))?;
+    let previous = series.iter().find(|p| p.date >= year_ago)?;
+
+    let total: BigDecimal = series.iter().map(|p| &p.amount).sum();
+    let average_amount = (total / BigDecimal::from(series.len() as i64)).round(2);
+    let next_expected_date = period.next(last.date)?;
+
+    let increase_threshold = previous.amount.abs() * BigDecimal::from(100 + PRICE_INCREASE_PERCENT)
+        / BigDecimal::from(100);
+
+    Some(Subscription {
+        source_account: source_account.to_string(),
+        counterparty: counterparty.to_string(),
+        currency: currency.to_string(),
+        period,
+        occurrences: series.len(),
+        first_date: first.date,
+        last_date: last.date,
+        next_expected_date,
+        annual_cost: (&average_amount * BigDecimal::from(period.per_year())).round(2),
+        average_amount,
+        last_amount: last.amount.clone(),
+        price_increased: last.amount.abs() > increase_threshold,
+        stopped: (today - next_expected_date).num_days() > period.grace_days(),
+    })
+}
+
+/// Analyses the whole `entry` history and replaces content of the `subscription` table.
+pub async fn detect(p: &Pool<Postgres>) -> anyhow::Result<usize> {
+    #[derive(sqlx::FromRow)]
+    struct Record {
+        source_account: String,
+        counterparty: String,
+        currency: String,
+        accounting_date: NaiveDate,
+        amount: BigDecimal,
+    }
+
+    let records: Vec<Record> = sqlx::query_as(
+        r#"
+        SELECT
+            source_account,
+            TRIM(sender_or_receiver) AS counterparty,
+            currency,
+            accounting_date,
+            amount
+        FROM entry
+        WHERE TRIM(sender_or_receiver) <> ''
+        ORDER BY accounting_date ASC
+        "#,
+    )
+    .fetch_all(p)
+    .await?;
+
+    // (source account, counterparty, currency, is expense), income and expenses
+    // from the same counterparty are separate series
+    type SeriesKey = (String, String, String, bool);
+    let mut groups: BTreeMap<SeriesKey, (String, Vec<Payment>)> = BTreeMap::new();
+    for r in records {
+        let key = (
+            r.source_account.clone(),
+            r.counterparty.to_lowercase(),
+            r.currency.clone(),
+            r.amount < BigDecimal::zero(),
+        );
+        groups
+            .entry(key)
+            .or_insert_with(|| (r.counterparty.clone(), Vec::new()))
+            .1
+            .push(Payment {
+                date: r.accounting_date,
+                amount: r.amount,
+            });
+    }
+
+    let today = chrono::Local::now().date_naive();
+    let subscriptions: Vec<Subscription> = groups
+        .iter()
+        .filter_map(
+            |((source_account, _, currency, _), (counterparty, payments))| {
+                summarize(source_account, counterparty, currency, payments, today)
+            },
+        )
+        .collect();
+
+    let mut tx = p.begin().await?;
+    sqlx::query("DELETE FROM subscription")
+        .execute(&mut *tx)
+        .await?;
+
+    for s in &subscriptions {
+        sqlx::query(
+            r#"
+            INSERT INTO subscription (
+                source_account,
+                counterparty,
+                currency,
+                period,
+                occurrences,
+                first_date,
+                last_date,
+                next_expected_date,
+                average_amount,
+                last_amount,
+                annual_cost,
+                price_increased,
+                stopped
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            "#,
+        )
+        .bind(&s.source_account)
+        .bind(&s.counterparty)
+        .bind(&s.currency)
+        .bind(s.period.as_str())
+        .bind(s.occurrences as i32)
+        .bind(s.first_date)
+        .bind(s.last_date)
+        .bind(s.next_expected_date)
+        .bind(&s.average_amount)
+        .bind(&s.last_amount)
+        .bind(&s.annual_cost)
+        .bind(s.price_increased)
+        .bind(s.stopped)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    log::info!("{} recurring payments detected", subscriptions.len());
+
+    Ok(subscriptions.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    fn payments(list: &[(&str, i64)]) -> Vec<Payment> {
+        list.iter()
+            .map(|(d, a)| Payment {
+                date: date(d),
+                amount: BigDecimal::from(*a),
+            })
+            .collect()
+    }
+
+    fn period(list: &[(&str, i64)], today: &str) -> Option<Period> {
+        find_recurring(&payments(list), date(today)).map(|(p, _)| p)
+    }
+
+    #[test]
+    fn detects_periods_from_intervals() {
+        let weekly = [
+            ("2024-01-01", -10),
+            ("2024-01-08", -10),
+            ("2024-01-15", -10),
+        ];
+        let monthly = [
+            ("2024-01-31", -50),
+            ("2024-02-29", -50),
+            ("2024-03-31", -50),
+            ("2024-04-30", -50),
+        ];
+        let yearly = [("2023-03-10", -300), ("2024-03-08", -300)];
+
+        assert_eq!(period(&weekly, "2024-01-20"), Some(Period::Weekly));
+        assert_eq!(period(&monthly, "2024-05-10"), Some(Period::Monthly));
+        assert_eq!(period(&yearly, "2024-04-01"), Some(Period::Yearly));
+    }
+
+    #[test]
+    fn needs_enough_payments() {
+        let monthly = [("2024-01-05", -50), ("2024-02-05", -50)];
+
+        assert_eq!(period(&monthly, "2024-03-01"), None);
+    }
+
+    #[test]
+    fn ignores_irregular_payments() {
+        let irregular = [
+            ("2024-01-01", -20),
+            ("2024-01-30", -20),
+            ("2024-03-15", -20),
+            ("2024-03-20", -20),
+            ("2024-06-01", -20),
+        ];
+
+        assert_eq!(period(&irregular, "2024-07-01"), None);
+    }
+
+    #[test]
+    fn leaves_out_payments_with_a_different_amount() {
+        let list = payments(&[
+            ("2024-01-05", -50),
+            ("2024-01-20", -500),
+            ("2024-02-05", -55),
+            ("2024-03-05", -50),
+        ]);
+
+        let (period, series) = find_recurring(&list, date("2024-03-10")).unwrap();
+
+        assert_eq!(period, Period::Monthly);
+        assert_eq!(
+            series.iter().map(|p| p.date).collect::<Vec<_>>(),
+            [date("2024-01-05"), date("2024-02-05"), date("2024-03-05")]
+        );
+    }
+
+    #[test]
+    fn summarizes_price_increase_and_stop() {
+        let list = payments(&[
+            ("2024-01-10", -40),
+            ("2024-02-10", -40),
+            ("2024-03-10", -40),
+            ("2024-04-10", -45),
+        ]);
+
+        let active = summarize("acc", "Netflix", "PLN", &list, date("2024-04-20")).unwrap();
+        assert_eq!(active.period, Period::Monthly);
+        assert_eq!(active.occurrences, 4);
+        assert_eq!(active.next_expected_date, date("2024-05-10"));
+        assert_eq!(active.average_amount, BigDecimal::from(-165) / 4);
+        assert_eq!(active.annual_cost, BigDecimal::from(-495));
+        assert!(active.price_increased);
+        assert!(!active.stopped);
+
+        let stopped = summarize("acc", "Netflix", "PLN", &list, date("2024-06-01")).unwrap();
+        assert!(stopped.stopped);
+    }
+}